@@ -0,0 +1,283 @@
+//! Converting between requests and `curl` command lines.
+//!
+//! [`parse_curl`] understands the options "Copy as cURL" in browsers and API
+//! docs use: `-X`, `-H`, `-d` and its variants, `--json`, `-u`, `-A`, `-e`,
+//! `-b`, `-G`, `-I`, `-k` and `--url`. Options that only change curl's own
+//! output, like `-s` or `-L`, are ignored.
+
+use std::fs;
+use std::io::{self, Read};
+
+use sip::http::{Client, HttpMethod, HttpRequest};
+
+/// Short options that take a value, which may be attached like `-XPOST`.
+const SHORT_WITH_VALUE: &str = "XHduAeb";
+
+/// Options curl uses for its own output, they don't change the request.
+const IGNORED: &[&str] = &[
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-L",
+    "--location",
+    "-v",
+    "--verbose",
+    "-i",
+    "--include",
+    "-f",
+    "--fail",
+    "--compressed",
+];
+
+/// Parses a `curl` command line into a request.
+///
+/// Like curl, `-d @file` and `--json @file` send the content of the file,
+/// or of stdin for `@-`.
+pub fn parse_curl(command: &str) -> Result<HttpRequest, &'static str> {
+    // Join lines continued with a backslash, shlex keeps the newline
+    let command = command.replace("\\\r\n", " ").replace("\\\n", " ");
+    let words = shlex::split(&command).ok_or("Unbalanced quotes in curl command")?;
+    let mut words = expand_short_options(words).into_iter().peekable();
+    words.next_if(|word| word == "curl");
+    let mut url = None;
+    let mut method = None;
+    let mut headers = Vec::new();
+    let mut data = Vec::new();
+    let mut json = false;
+    let mut user = None;
+    let mut get = false;
+    let mut insecure = false;
+
+    while let Some(word) = words.next() {
+        let mut value = || words.next().ok_or("Missing value for curl option");
+        match word.as_str() {
+            "-X" | "--request" => method = Some(HttpMethod::from_str(&value()?)),
+            "-H" | "--header" => headers.push(value()?),
+            "-d" | "--data" | "--data-ascii" => {
+                let value = value()?;
+                match value.strip_prefix('@') {
+                    // curl drops the line breaks of the file
+                    Some(file) => data.push(read_data(file)?.replace(['\r', '\n'], "")),
+                    None => data.push(value),
+                }
+            }
+            "--data-binary" | "--json" => {
+                json |= word == "--json";
+                let value = value()?;
+                match value.strip_prefix('@') {
+                    Some(file) => data.push(read_data(file)?),
+                    None => data.push(value),
+                }
+            }
+            "--data-raw" => data.push(value()?),
+            "-u" | "--user" => user = Some(value()?),
+            "-A" | "--user-agent" => headers.push(format!("User-Agent: {}", value()?)),
+            "-e" | "--referer" => headers.push(format!("Referer: {}", value()?)),
+            "-b" | "--cookie" => {
+                let cookie = value()?;
+                if !cookie.contains('=') {
+                    return Err("Cookie files are not supported, use -b 'name=value'");
+                }
+                headers.push(format!("Cookie: {}", cookie));
+            }
+            "-G" | "--get" => get = true,
+            "-I" | "--head" => method = method.or(Some(HttpMethod::Head)),
+            "-k" | "--insecure" => insecure = true,
+            "--url" => url = Some(value()?),
+            option if IGNORED.contains(&option) => {}
+            option if option.starts_with('-') => return Err("Unsupported curl option"),
+            _ => url = Some(word),
+        }
+    }
+
+    let mut url = url.ok_or("Missing URL in curl command")?;
+    if get && !data.is_empty() {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        url.push_str(&data.join("&"));
+        data.clear();
+    }
+    let method = method.unwrap_or(match data.is_empty() {
+        true => HttpMethod::Get,
+        false => HttpMethod::Post,
+    });
+
+    let mut builder = Client::request(method, &url);
+    if !data.is_empty() {
+        let content_type = match json {
+            true => "application/json",
+            false => "application/x-www-form-urlencoded",
+        };
+        builder = builder.header("Content-Type", content_type);
+        if json {
+            builder = builder.header("Accept", "application/json");
+        }
+        builder = builder.body(data.join("&"));
+    }
+    if let Some(user) = user {
+        let (user, password) = user.split_once(':').unwrap_or((user.as_str(), ""));
+        builder = builder.basic_auth(user, password);
+    }
+    for header in headers {
+        let (k, v) = header.split_once(':').ok_or("Invalid curl header")?;
+        builder = builder.header(k.trim(), v.trim());
+    }
+    let mut request = builder.build()?;
+    request.tls.insecure = insecure;
+    Ok(request)
+}
+
+/// Writes the request as a `curl` command line.
+///
+/// `Host` and `Content-Length` are left to curl unless `Host` differs from
+/// the URL.
+pub fn to_curl(request: &HttpRequest) -> String {
+    let mut words = vec!["curl".to_string()];
+    if request.method != HttpMethod::Get {
+        words.push("-X".to_string());
+        words.push(request.method.to_str().to_string());
+    }
+    words.push(quote(&request.url()));
+    for (k, v) in written_headers(request) {
+        words.push("-H".to_string());
+        words.push(quote(&format!("{}: {}", k, v)));
+    }
+    if !request.body.is_empty() {
+        words.push("--data-raw".to_string());
+        words.push(quote(&String::from_utf8_lossy(&request.body)));
+    }
+    if request.tls.insecure {
+        words.push("-k".to_string());
+    }
+    words.join(" ")
+}
+
+/// Reads the data of `-d @file`, `-` is stdin.
+fn read_data(file: &str) -> Result<String, &'static str> {
+    let mut data = String::new();
+    match file {
+        "-" => io::stdin().read_to_string(&mut data).map(|_| data),
+        file => fs::read_to_string(file),
+    }
+    .map_err(|_| "Unable to read the data file")
+}
+
+/// Returns the headers worth writing out: all but `Content-Length` and a
+/// `Host` that matches the URL.
+fn written_headers(request: &HttpRequest) -> impl Iterator<Item = (&str, &str)> {
+    let authority = request.host.rsplit("://").next().unwrap_or_default();
+    request
+        .headers
+        .iter()
+        .map(|(k, v)| (&**k, v.as_str()))
+        .filter(move |(k, v)| match k.to_lowercase().as_str() {
+            "content-length" => false,
+            "host" => *v != authority,
+            _ => true,
+        })
+}
+
+/// Splits grouped short options, so `-sSL` becomes `-s -S -L` and `-XPOST`
+/// becomes `-X POST`.
+fn expand_short_options(words: Vec<String>) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for word in words {
+        let Some(options) = word
+            .strip_prefix('-')
+            .filter(|o| o.len() > 1 && !o.starts_with('-'))
+        else {
+            expanded.push(word);
+            continue;
+        };
+        for (i, option) in options.char_indices() {
+            expanded.push(format!("-{}", option));
+            if SHORT_WITH_VALUE.contains(option) {
+                let value = &options[i + option.len_utf8()..];
+                if !value.is_empty() {
+                    expanded.push(value.to_string());
+                }
+                break;
+            }
+        }
+    }
+    expanded
+}
+
+/// Quotes `word` for POSIX shells, only when it needs it.
+fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@,%+".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_curl() {
+        let request = parse_curl(
+            "curl 'https://api.example.com/users?v=2' \\\n  \
+             -H 'Accept: application/json' -sSL \\\n  \
+             -u tea:pot --json '{\"name\": \"tea\"}'",
+        )
+        .unwrap();
+        assert_eq!(request.method, HttpMethod::Post);
+        assert_eq!(request.url(), "https://api.example.com/users?v=2");
+        assert_eq!(request.headers.get("accept").unwrap(), "application/json");
+        assert_eq!(
+            request.headers.get("content-type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(
+            request.headers.get("authorization").unwrap(),
+            "Basic dGVhOnBvdA=="
+        );
+        assert_eq!(request.body, b"{\"name\": \"tea\"}");
+
+        let request = parse_curl("curl -G -d a=1 -d b=2 -XDELETE -k localhost:8080").unwrap();
+        assert_eq!(request.method, HttpMethod::Delete);
+        assert_eq!(request.url(), "http://localhost:8080/?a=1&b=2");
+        assert!(request.body.is_empty());
+        assert!(request.tls.insecure);
+
+        assert!(parse_curl("curl -H").is_err());
+        assert!(parse_curl("curl 'https://example.com").is_err());
+        assert!(parse_curl("curl --fancy https://example.com").is_err());
+        assert!(parse_curl("curl -s").is_err());
+    }
+
+    #[test]
+    fn test_data_file() {
+        let path = std::env::temp_dir().join(format!("sip-curl-{}.txt", std::process::id()));
+        fs::write(&path, "a=1\nb=2\n").unwrap();
+        let file = path.display();
+        let request = parse_curl(&format!("curl localhost -d @{} -d c=3", file)).unwrap();
+        assert_eq!(request.body, b"a=1b=2&c=3");
+        let request = parse_curl(&format!("curl localhost --data-binary @{}", file)).unwrap();
+        assert_eq!(request.body, b"a=1\nb=2\n");
+        let request = parse_curl(&format!("curl localhost --data-raw @{}", file)).unwrap();
+        assert_eq!(request.body, format!("@{}", file).as_bytes());
+        fs::remove_file(&path).unwrap();
+        assert!(parse_curl(&format!("curl localhost -d @{}", file)).is_err());
+    }
+
+    #[test]
+    fn test_to_curl() {
+        let request =
+            parse_curl("curl -X PATCH https://example.com/a -H 'X-Id: 1' -d \"it's\"").unwrap();
+        let command = to_curl(&request);
+        assert_eq!(
+            command,
+            "curl -X PATCH https://example.com/a \
+             -H 'Content-Type: application/x-www-form-urlencoded' \
+             -H 'X-Id: 1' --data-raw 'it'\\''s'"
+        );
+        let parsed = parse_curl(&command).unwrap();
+        assert_eq!(parsed.url(), request.url());
+        assert_eq!(parsed.headers, request.headers);
+        assert_eq!(parsed.body, request.body);
+    }
+}
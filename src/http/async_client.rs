@@ -0,0 +1,263 @@
+//! Sending requests on tokio without blocking the runtime.
+//!
+//! [`send`] does what `brew` does with async sockets: it resolves, races the
+//! addresses, does the TLS handshake and feeds what the server sends to the
+//! same sans-IO [`HttpResponseBuilder`], so both paths parse responses the
+//! same way. HTTPS needs the `async-native-tls` or `async-rustls` feature.
+//! Proxies are not supported yet.
+
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout};
+
+use super::brew::{CLOSED_ERROR, CONNECT_ERROR, CONTINUE_TIMEOUT, READ_ERROR, TIMEOUT_ERROR};
+use super::request::HttpRequest;
+use super::response::{HttpResponse, HttpResponseBuilder};
+use super::status::HttpStatus;
+use super::tls;
+use super::transport::{CONNECTION_ATTEMPT_DELAY, interleave};
+
+pub(crate) trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncStream for T {}
+
+/// Sends the request and returns the parsed response.
+///
+/// Follows the request's `retry` policy like `brew`, sleeping on the tokio
+/// timer between attempts.
+pub async fn send(request: &HttpRequest) -> Result<HttpResponse, &'static str> {
+    let policy = match &request.retry {
+        Some(policy) if policy.allows(&request.method) => policy,
+        _ => return send_once(request).await,
+    };
+    let start = Instant::now();
+    let mut retry = 0;
+    loop {
+        let result = send_once(request).await;
+        match policy.next_wait(retry, start.elapsed(), &result) {
+            Some(wait) => sleep(wait).await,
+            None => return result,
+        }
+        retry += 1;
+    }
+}
+
+async fn send_once(request: &HttpRequest) -> Result<HttpResponse, &'static str> {
+    let (ssl, host, port) = request.endpoint()?;
+    if request.unix_socket.is_none()
+        && request
+            .proxy
+            .as_ref()
+            .is_some_and(|p| !p.bypasses(&host, port))
+    {
+        return Err("Proxies are not supported by the async client");
+    }
+
+    let mut builder = HttpResponseBuilder::for_method(&request.method);
+    let mut stream: Box<dyn AsyncStream> = match &request.unix_socket {
+        Some(path) => connect_unix(path).await?,
+        None => {
+            let (stream, addr) = connect(request, &host, port).await?;
+            builder.connection_mut().remote_addr = Some(addr);
+            Box::new(stream)
+        }
+    };
+    if ssl {
+        let (tls_stream, info) = tls::connect_async(&request.tls, &host, stream).await?;
+        builder.connection_mut().tls = Some(info);
+        stream = tls_stream;
+    }
+
+    // Sign a copy so the signature covers the final headers and body
+    let prepared;
+    let missing_host = !request.headers.contains_key("host");
+    let request = if request.signer.is_some() || missing_host {
+        let mut copy = request.clone();
+        if missing_host {
+            copy.headers.insert("Host", &request.host_header()?);
+        }
+        if let Some(signer) = &request.signer {
+            signer.sign(&mut copy)?;
+        }
+        prepared = copy;
+        &prepared
+    } else {
+        request
+    };
+
+    write(&mut stream, request.head(&request.target()).as_bytes()).await?;
+    if request.expects_continue() && !request.body.is_empty() {
+        stream.flush().await.map_err(|_| CLOSED_ERROR)?;
+        if wait_continue(&mut stream, &mut builder).await? {
+            return builder.get().ok_or(TIMEOUT_ERROR);
+        }
+    }
+    write(&mut stream, &request.body).await?;
+    stream.flush().await.map_err(|_| CLOSED_ERROR)?;
+
+    let mut buffer = [0u8; 4096];
+    loop {
+        let n = timeout(request.read_timeout(), stream.read(&mut buffer))
+            .await
+            .map_err(|_| TIMEOUT_ERROR)?
+            .map_err(|_| READ_ERROR)?;
+        if builder.append(&buffer[..n])? {
+            break;
+        }
+        if n == 0 {
+            return Err(CLOSED_ERROR);
+        }
+    }
+    builder.get().ok_or(TIMEOUT_ERROR)
+}
+
+async fn write(stream: &mut Box<dyn AsyncStream>, bytes: &[u8]) -> Result<(), &'static str> {
+    stream.write_all(bytes).await.map_err(|_| CLOSED_ERROR)
+}
+
+/// Resolves `host` off the runtime and races its addresses like
+/// `connect_any`.
+async fn connect(
+    request: &HttpRequest,
+    host: &str,
+    port: u16,
+) -> Result<(TcpStream, SocketAddr), &'static str> {
+    let resolver = request.resolver.clone();
+    let name = host.to_string();
+    let resolved = tokio::task::spawn_blocking(move || resolver.resolve(&name, port))
+        .await
+        .map_err(|_| "Unable to resolve domain")??;
+    let addrs = interleave(&request.usable_addrs(resolved)?);
+
+    let mut attempts = JoinSet::new();
+    for (i, addr) in addrs.into_iter().enumerate() {
+        attempts.spawn(async move {
+            sleep(CONNECTION_ATTEMPT_DELAY * i as u32).await;
+            TcpStream::connect(addr).await.map(|stream| (stream, addr))
+        });
+    }
+    let first = async {
+        while let Some(attempt) = attempts.join_next().await {
+            if let Ok(Ok(connected)) = attempt {
+                return Some(connected);
+            }
+        }
+        None
+    };
+    // Dropping the set aborts the attempts still running
+    match timeout(request.connect_timeout(), first).await {
+        Ok(Some(connected)) => Ok(connected),
+        _ => Err(CONNECT_ERROR),
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &std::path::Path) -> Result<Box<dyn AsyncStream>, &'static str> {
+    let stream = tokio::net::UnixStream::connect(path)
+        .await
+        .map_err(|_| "Error connecting to unix socket")?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: &std::path::Path) -> Result<Box<dyn AsyncStream>, &'static str> {
+    Err("Unix sockets are not supported on this platform")
+}
+
+/// Async version of `brew`'s `wait_continue`: returns `Ok(true)` if the final
+/// response already arrived and the body must not be sent.
+async fn wait_continue(
+    stream: &mut Box<dyn AsyncStream>,
+    builder: &mut HttpResponseBuilder,
+) -> Result<bool, &'static str> {
+    let mut buffer = [0u8; 4096];
+    loop {
+        let n = match timeout(CONTINUE_TIMEOUT, stream.read(&mut buffer)).await {
+            Err(_) => return Ok(false),
+            Ok(Ok(0)) => return Err(CLOSED_ERROR),
+            Ok(Ok(n)) => n,
+            Ok(Err(_)) => return Err(READ_ERROR),
+        };
+        if builder.append(&buffer[..n])? {
+            return Ok(true);
+        }
+        if builder
+            .informational()
+            .iter()
+            .any(|r| r.status == HttpStatus::Continue)
+        {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{HttpMethod, RetryPolicy};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// Serves one canned response to each of `responses` connections.
+    fn serve(responses: &'static [&'static [u8]]) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0u8; 1024];
+                let _ = stream.read(&mut buffer).unwrap();
+                stream.write_all(response).unwrap();
+            }
+        });
+        port
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_send() {
+        let port =
+            serve(&[b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n"]);
+        let request = HttpRequest::new(HttpMethod::Get, &format!("localhost:{}", port), "/");
+        let response = runtime().block_on(send(&request)).unwrap();
+        assert_eq!(response.status, HttpStatus::OK);
+        assert_eq!(response.content, b"ok");
+        assert_eq!(
+            response.connection.remote_addr,
+            Some(SocketAddr::from(([127, 0, 0, 1], port)))
+        );
+    }
+
+    #[test]
+    fn test_send_retries() {
+        let port = serve(&[
+            b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\n\r\n",
+            b"HTTP/1.1 204 No Content\r\n\r\n",
+        ]);
+        let mut request = HttpRequest::new(HttpMethod::Get, &format!("127.0.0.1:{}", port), "/");
+        request.retry = Some(RetryPolicy::new(1));
+        let response = runtime().block_on(send(&request)).unwrap();
+        assert_eq!(response.status, HttpStatus::NoContent);
+    }
+
+    #[test]
+    fn test_connection_refused() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let request = HttpRequest::new(HttpMethod::Get, &format!("127.0.0.1:{}", port), "/");
+        let result = runtime().block_on(send(&request));
+        assert_eq!(result.err(), Some("Error connecting to server"));
+    }
+}
@@ -0,0 +1,413 @@
+//! Request authentication.
+//!
+//! An [`Auth`] adds credentials to a request before it is sent and may look
+//! at a `401 Unauthorized` answer to decide whether the request should be
+//! retried, which is how challenge based schemes like Digest work.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use super::{HttpRequest, HttpResponse, HttpStatus};
+
+/// Adds credentials to requests, and reacts to `401` answers.
+pub trait Auth {
+    /// Adds credentials to `request`.
+    fn apply(&mut self, request: &mut HttpRequest) -> Result<(), &'static str>;
+
+    /// Handles a `401` answer to `request`.
+    ///
+    /// Returns true if the request should be sent again after a new `apply`.
+    fn challenge(&mut self, _request: &HttpRequest, _response: &HttpResponse) -> bool {
+        false
+    }
+}
+
+/// HTTP Basic authentication (RFC 7617).
+pub struct BasicAuth {
+    /// User name.
+    pub user: String,
+    /// Password, sent as is.
+    pub password: String,
+}
+
+impl BasicAuth {
+    /// Creates Basic credentials for `user` and `password`.
+    pub fn new(user: &str, password: &str) -> Self {
+        BasicAuth {
+            user: user.to_string(),
+            password: password.to_string(),
+        }
+    }
+}
+
+impl Auth for BasicAuth {
+    fn apply(&mut self, request: &mut HttpRequest) -> Result<(), &'static str> {
+        let credentials = STANDARD.encode(format!("{}:{}", self.user, self.password));
+        request
+            .headers
+            .insert("Authorization", &format!("Basic {}", credentials));
+        Ok(())
+    }
+}
+
+/// Bearer token authentication (RFC 6750).
+pub struct BearerAuth {
+    /// Token sent after `Bearer`.
+    pub token: String,
+}
+
+impl BearerAuth {
+    /// Creates a provider sending `token`.
+    pub fn new(token: &str) -> Self {
+        BearerAuth {
+            token: token.to_string(),
+        }
+    }
+}
+
+impl Auth for BearerAuth {
+    fn apply(&mut self, request: &mut HttpRequest) -> Result<(), &'static str> {
+        request
+            .headers
+            .insert("Authorization", &format!("Bearer {}", self.token));
+        Ok(())
+    }
+}
+
+/// Parameters of a `WWW-Authenticate: Digest` challenge.
+#[derive(Debug, Clone, PartialEq)]
+struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: String,
+    qop: Option<String>,
+}
+
+/// HTTP Digest authentication (RFC 7616).
+///
+/// Supports the MD5 and SHA-256 algorithms, their `-sess` variants and the
+/// `auth` and `auth-int` qualities of protection.
+pub struct DigestAuth {
+    /// User name.
+    pub user: String,
+    /// Password, only ever sent hashed.
+    pub password: String,
+    challenge: Option<DigestChallenge>,
+    nonce_count: u32,
+}
+
+impl DigestAuth {
+    /// Creates Digest credentials, the challenge comes with the first `401`.
+    pub fn new(user: &str, password: &str) -> Self {
+        DigestAuth {
+            user: user.to_string(),
+            password: password.to_string(),
+            challenge: None,
+            nonce_count: 0,
+        }
+    }
+
+    fn authorization(
+        &self,
+        challenge: &DigestChallenge,
+        request: &HttpRequest,
+        nonce_count: u32,
+        cnonce: &str,
+    ) -> Result<String, &'static str> {
+        let digest: fn(&[u8]) -> String = match challenge.algorithm.to_uppercase().as_str() {
+            "MD5" | "MD5-SESS" => |data| to_hex(&Md5::digest(data)),
+            "SHA-256" | "SHA-256-SESS" => |data| to_hex(&Sha256::digest(data)),
+            _ => return Err("Unsupported digest algorithm"),
+        };
+        let hash = |data: String| digest(data.as_bytes());
+        let uri = request.target();
+        let nc = format!("{:08x}", nonce_count);
+        let method = request.method.to_str();
+
+        let mut ha1 = hash(format!(
+            "{}:{}:{}",
+            self.user, challenge.realm, self.password
+        ));
+        if challenge.algorithm.to_uppercase().ends_with("-SESS") {
+            ha1 = hash(format!("{}:{}:{}", ha1, challenge.nonce, cnonce));
+        }
+        let ha2 = match challenge.qop.as_deref() {
+            Some("auth-int") => hash(format!("{}:{}:{}", method, uri, digest(&request.body))),
+            _ => hash(format!("{}:{}", method, uri)),
+        };
+        let response = match challenge.qop.as_deref() {
+            Some(qop) => hash(format!(
+                "{}:{}:{}:{}:{}:{}",
+                ha1, challenge.nonce, nc, cnonce, qop, ha2
+            )),
+            None => hash(format!("{}:{}:{}", ha1, challenge.nonce, ha2)),
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+            self.user, challenge.realm, challenge.nonce, uri, challenge.algorithm, response
+        );
+        if let Some(qop) = &challenge.qop {
+            header.push_str(&format!(", qop={}, nc={}, cnonce=\"{}\"", qop, nc, cnonce));
+        }
+        if let Some(opaque) = &challenge.opaque {
+            header.push_str(&format!(", opaque=\"{}\"", opaque));
+        }
+        Ok(header)
+    }
+}
+
+impl Auth for DigestAuth {
+    fn apply(&mut self, request: &mut HttpRequest) -> Result<(), &'static str> {
+        // Nothing to send until the server has challenged us
+        let Some(challenge) = self.challenge.clone() else {
+            return Ok(());
+        };
+        self.nonce_count += 1;
+        let header = self.authorization(&challenge, request, self.nonce_count, &new_cnonce()?)?;
+        request.headers.insert("Authorization", &header);
+        Ok(())
+    }
+
+    fn challenge(&mut self, _request: &HttpRequest, response: &HttpResponse) -> bool {
+        let Some(challenge) = response
+            .headers
+            .get_all("www-authenticate")
+            .find_map(|v| parse_digest_challenge(v))
+        else {
+            return false;
+        };
+        // A second challenge for the same nonce means the credentials are wrong,
+        // unless the server flags the nonce as stale
+        let stale = response
+            .headers
+            .get_all("www-authenticate")
+            .any(|v| v.to_lowercase().contains("stale=true"));
+        let retry = match &self.challenge {
+            Some(previous) => previous.nonce != challenge.nonce && stale,
+            None => true,
+        };
+        if self.challenge.as_ref().map(|c| &c.nonce) != Some(&challenge.nonce) {
+            self.nonce_count = 0;
+        }
+        self.challenge = Some(challenge);
+        retry
+    }
+}
+
+impl HttpRequest {
+    /// Sends the request with `auth`, answering one authentication challenge.
+    pub fn brew_with_auth(&mut self, auth: &mut dyn Auth) -> Result<HttpResponse, &'static str> {
+        auth.apply(self)?;
+        let response = self.brew()?;
+        if response.status == HttpStatus::Unauthorized && auth.challenge(self, &response) {
+            auth.apply(self)?;
+            return self.brew();
+        }
+        Ok(response)
+    }
+}
+
+/// Parses a `WWW-Authenticate` value, returning `None` unless it's a Digest challenge.
+fn parse_digest_challenge(header: &str) -> Option<DigestChallenge> {
+    let (scheme, params) = header.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("digest") {
+        return None;
+    }
+    let mut realm = None;
+    let mut nonce = None;
+    let mut opaque = None;
+    let mut algorithm = "MD5".to_string();
+    let mut qop = None;
+    for (k, v) in parse_auth_params(params) {
+        match k.to_lowercase().as_str() {
+            "realm" => realm = Some(v),
+            "nonce" => nonce = Some(v),
+            "opaque" => opaque = Some(v),
+            "algorithm" => algorithm = v,
+            "qop" => {
+                let offered: Vec<String> = v.split(',').map(|q| q.trim().to_lowercase()).collect();
+                qop = ["auth", "auth-int"]
+                    .into_iter()
+                    .find(|q| offered.iter().any(|o| o == q))
+                    .map(str::to_string);
+            }
+            _ => {}
+        }
+    }
+    Some(DigestChallenge {
+        realm: realm?,
+        nonce: nonce?,
+        opaque,
+        algorithm,
+        qop,
+    })
+}
+
+/// Splits `k=v, k="quoted, value"` auth parameters.
+fn parse_auth_params(params: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let mut chars = params.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+        if key.is_empty() {
+            break;
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    _ => value.push(c),
+                }
+            }
+        } else {
+            value = chars.by_ref().take_while(|c| *c != ',').collect();
+        }
+        result.push((key.trim().to_string(), value.trim().to_string()));
+    }
+    result
+}
+
+/// Returns 8 random bytes from the OS as hex, so the cnonce can't be guessed.
+fn new_cnonce() -> Result<String, &'static str> {
+    let mut bytes = [0u8; 8];
+    getrandom::fill(&mut bytes).map_err(|_| "Error generating cnonce")?;
+    Ok(to_hex(&bytes))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpMethod;
+
+    #[test]
+    fn test_basic() {
+        let mut request = HttpRequest::new(HttpMethod::Get, "localhost", "/");
+        BasicAuth::new("Aladdin", "open sesame")
+            .apply(&mut request)
+            .unwrap();
+        assert_eq!(
+            request.headers.get("authorization").unwrap(),
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+    }
+
+    #[test]
+    fn test_digest_md5() {
+        // RFC 2617 §3.5
+        let challenge = parse_digest_challenge(
+            "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        )
+        .unwrap();
+        assert_eq!(challenge.qop.as_deref(), Some("auth"));
+        let request = HttpRequest::new(HttpMethod::Get, "localhost", "/dir/index.html");
+        let auth = DigestAuth::new("Mufasa", "Circle Of Life");
+        let header = auth
+            .authorization(&challenge, &request, 1, "0a4f113b")
+            .unwrap();
+        assert!(header.contains("response=\"6629fae49393a05397450978507c4ef1\""));
+        assert!(header.contains("nc=00000001"));
+        assert!(header.contains("opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""));
+    }
+
+    #[test]
+    fn test_digest_sha256() {
+        // RFC 7616 §3.9.1
+        let challenge = parse_digest_challenge(
+            "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", algorithm=SHA-256, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+        )
+        .unwrap();
+        let request = HttpRequest::new(HttpMethod::Get, "localhost", "/dir/index.html");
+        let auth = DigestAuth::new("Mufasa", "Circle of Life");
+        let header = auth
+            .authorization(
+                &challenge,
+                &request,
+                1,
+                "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+            )
+            .unwrap();
+        assert!(header.contains(
+            "response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\""
+        ));
+    }
+
+    #[test]
+    fn test_digest_auth_int() {
+        let mut challenge =
+            parse_digest_challenge("Digest realm=\"r\", nonce=\"n\", qop=\"auth-int\"").unwrap();
+        assert_eq!(challenge.qop.as_deref(), Some("auth-int"));
+        let mut request = HttpRequest::new(HttpMethod::Post, "localhost", "/");
+        request.body = b"payload".to_vec();
+        let auth = DigestAuth::new("u", "p");
+        let with_body = auth.authorization(&challenge, &request, 1, "c").unwrap();
+        request.body = b"other".to_vec();
+        assert_ne!(
+            with_body,
+            auth.authorization(&challenge, &request, 1, "c").unwrap()
+        );
+
+        challenge.qop = Some("auth".to_string());
+        let without_body = auth.authorization(&challenge, &request, 1, "c").unwrap();
+        request.body = b"payload".to_vec();
+        assert_eq!(
+            without_body,
+            auth.authorization(&challenge, &request, 1, "c").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_digest_challenge_flow() {
+        let mut builder = crate::http::response::HttpResponseBuilder::new();
+        builder
+            .append(b"HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"x\"\r\nWWW-Authenticate: Digest realm=\"r\", nonce=\"abc\", qop=\"auth\"\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        let response = builder.get().unwrap();
+        let mut request = HttpRequest::new(HttpMethod::Get, "localhost", "/");
+        let mut auth = DigestAuth::new("u", "p");
+        auth.apply(&mut request).unwrap();
+        assert!(!request.headers.contains_key("authorization"));
+
+        assert!(auth.challenge(&request, &response));
+        auth.apply(&mut request).unwrap();
+        assert!(
+            request
+                .headers
+                .get("authorization")
+                .unwrap()
+                .contains("nc=00000001")
+        );
+        auth.apply(&mut request).unwrap();
+        assert!(
+            request
+                .headers
+                .get("authorization")
+                .unwrap()
+                .contains("nc=00000002")
+        );
+
+        // Same nonce again: the credentials were rejected
+        assert!(!auth.challenge(&request, &response));
+    }
+
+    #[test]
+    fn test_cnonce() {
+        let cnonce = new_cnonce().unwrap();
+        assert_eq!(cnonce.len(), 16);
+        assert!(cnonce.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(cnonce, new_cnonce().unwrap());
+    }
+}
@@ -6,18 +6,45 @@
 
 use std::fmt::{self, Display, Formatter};
 use std::io::{Read, Write};
-use std::net::{TcpStream, ToSocketAddrs};
+use std::net::SocketAddr;
 use std::time::Duration;
 
-use native_tls::TlsConnector;
-
+use super::proxy::{ProxyScheme, authority};
 use super::request::HttpRequest;
 use super::response::{HttpResponse, HttpResponseBuilder};
+use super::status::HttpStatus;
+use super::tls;
+use super::transport::{Socket, connect_any};
+
+pub(crate) const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+pub(crate) const READ_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long to wait for `100 Continue` before sending the body anyway.
+pub(crate) const CONTINUE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Errors a new attempt may not hit, see `is_transient`
+pub(crate) const CONNECT_ERROR: &str = "Error connecting to server";
+pub(crate) const CLOSED_ERROR: &str = "Connection closed";
+pub(crate) const READ_ERROR: &str = "Error reading";
+pub(crate) const TIMEOUT_ERROR: &str = "Read timeout";
+pub(crate) const PROXY_CLOSED_ERROR: &str = "Proxy closed the connection";
+
+/// Returns true if `error`, from `brew` or `brew_async`, may go away when the
+/// request is sent again.
+pub(crate) fn is_transient(error: &str) -> bool {
+    [
+        CONNECT_ERROR,
+        CLOSED_ERROR,
+        READ_ERROR,
+        TIMEOUT_ERROR,
+        PROXY_CLOSED_ERROR,
+    ]
+    .contains(&error)
+}
 
-// use super::status::HttpStatus;
 // use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 //
-trait StreamRW: Read + Write {}
+pub(crate) trait StreamRW: Read + Write {}
 impl<T: Read + Write> StreamRW for T {}
 
 impl Display for HttpRequest {
@@ -25,11 +52,21 @@ impl Display for HttpRequest {
     ///
     /// This includes method, path with optional query args, headers, and optional body.
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.head(&self.target()))?;
+        write!(f, "{}", &self.text().unwrap_or_default())?;
+        write!(f, "\r\n")
+    }
+}
+
+impl HttpRequest {
+    /// Returns the request target: the path plus the query arguments.
+    pub fn target(&self) -> String {
         // Add query parameters to the path if needed
         let path = if self.args.is_empty() {
             self.path.clone()
         } else {
-            let mut path = format!("{}?", self.path);
+            let separator = if self.path.contains('?') { '&' } else { '?' };
+            let mut path = format!("{}{}", self.path, separator);
             for (k, v) in &self.args {
                 path.push_str(&format!("{}={}&", k, v));
             }
@@ -39,95 +76,196 @@ impl Display for HttpRequest {
             path
         };
 
-        let path = if path.is_empty() {
+        if path.is_empty() {
             "/".to_string()
         } else {
             path
-        };
+        }
+    }
 
-        write!(f, "{} {} HTTP/1.1\r\n", self.method.to_str(), path)?;
+    /// Returns the request line for `target` and the headers, terminated by
+    /// the empty line.
+    pub(crate) fn head(&self, target: &str) -> String {
+        let mut head = format!("{} {} HTTP/1.1\r\n", self.method.to_str(), target);
         for (k, v) in &self.headers {
-            write!(f, "{}: {}\r\n", k, v)?;
+            head.push_str(&format!("{}: {}\r\n", k, v));
         }
-        write!(f, "\r\n")?;
-        write!(f, "{}", &self.text().unwrap_or_default())?;
-        write!(f, "\r\n")
+        head.push_str("\r\n");
+        head
+    }
+
+    /// Returns the `Host` header value for the endpoint, without the default
+    /// port of the scheme.
+    pub(crate) fn host_header(&self) -> Result<String, &'static str> {
+        let (ssl, host, port) = self.endpoint()?;
+        Ok(match (ssl, port) {
+            (true, 443) | (false, 80) if host.contains(':') => format!("[{}]", host),
+            (true, 443) | (false, 80) => host,
+            _ => authority(&host, port),
+        })
+    }
+
+    pub(crate) fn connect_timeout(&self) -> Duration {
+        self.timeout.unwrap_or(CONNECT_TIMEOUT)
+    }
+
+    pub(crate) fn read_timeout(&self) -> Duration {
+        self.timeout.unwrap_or(READ_TIMEOUT)
+    }
+
+    /// Returns true if the request asks the server to confirm before the body is sent.
+    pub(crate) fn expects_continue(&self) -> bool {
+        self.headers
+            .get("expect")
+            .is_some_and(|v| v.eq_ignore_ascii_case("100-continue"))
+    }
+
+    /// Returns the addresses to connect to for `host` and `port`.
+    fn resolve_addrs(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, &'static str> {
+        self.usable_addrs(self.resolver.resolve(host, port)?)
+    }
+
+    /// Drops the resolved addresses that can't or mustn't be used.
+    pub(crate) fn usable_addrs(
+        &self,
+        resolved_addrs: Vec<SocketAddr>,
+    ) -> Result<Vec<SocketAddr>, &'static str> {
+        let addrs: Vec<SocketAddr> = resolved_addrs
+            .into_iter()
+            .filter(|addr| addr.port() != 0 && !addr.ip().is_unspecified())
+            .filter(|addr| self.ip_version.is_none_or(|v| v.matches(addr)))
+            .collect();
+        if addrs.is_empty() {
+            return Err("No valid address found");
+        }
+        Ok(addrs)
+    }
+
+    /// Opens a TCP connection to `host` and `port`, racing its addresses.
+    fn connect(&self, host: &str, port: u16) -> Result<(Socket, SocketAddr), &'static str> {
+        let addrs = self.resolve_addrs(host, port)?;
+        let (stream, socket_addr) = connect_any(&addrs, self.connect_timeout())?;
+        Ok((Socket::Tcp(stream), socket_addr))
     }
-}
 
-impl HttpRequest {
     /// Sends the request to a remote server and returns a parsed response.
     ///
-    /// Supports only `http://` (not `https://`). Attempts to resolve the domain
-    /// and open a TCP connection. Times out after 5 seconds.
+    /// A missing `Host` header is filled in from the URL.
+    ///
+    /// With a `retry` policy, transient failures are retried as long as the
+    /// policy allows replaying the method.
     pub fn brew(&self) -> Result<HttpResponse, &'static str> {
-        let mut addr = self.host.clone();
-        let mut ssl = self.ssl;
-        // Strip protocol prefix
-        if let Some(stripped) = addr.strip_prefix("http://") {
-            addr = stripped.to_string();
-        } else if addr.starts_with("https://") {
-            ssl = true;
+        match &self.retry {
+            Some(policy) if policy.allows(&self.method) => policy.run(|| self.send()),
+            _ => self.send(),
         }
+    }
 
-        // Add port if missing
-        if !addr.contains(':') {
-            if ssl {
-                addr.push_str(":443");
-            } else {
-                addr.push_str(":80");
-            }
-        }
+    /// Sends the request once.
+    ///
+    /// Uses TLS for `https://` hosts or when `ssl` is set. Resolves the domain
+    /// with `resolver` and opens a TCP connection, through `proxy` if set.
+    /// Gives up after `timeout`, or 5 seconds to connect and 20 per read.
+    ///
+    /// With `unix_socket` the request goes to that socket instead, the host
+    /// is only used for TLS and the headers.
+    fn send(&self) -> Result<HttpResponse, &'static str> {
+        let (ssl, host, port) = self.endpoint()?;
+        let proxy = self
+            .proxy
+            .as_ref()
+            .filter(|p| self.unix_socket.is_none() && !p.bypasses(&host, port));
 
-        let addr = addr.split("/").next().unwrap();
-        // Resolve address
-        let addr = if addr.starts_with("localhost") {
-            addr.replace("localhost", "127.0.0.1").to_string()
-        } else {
-            addr.to_string()
+        // Connect to server
+        let (stream, socket_addr) = match (&self.unix_socket, proxy) {
+            (Some(path), _) => (Socket::unix(path)?, None),
+            (None, Some(proxy)) => {
+                let (stream, addr) = self.connect(&proxy.host, proxy.port)?;
+                (stream, Some(addr))
+            }
+            (None, None) => {
+                let (stream, addr) = self.connect(&host, port)?;
+                (stream, Some(addr))
+            }
         };
+        let _ = stream.set_read_timeout(Some(self.read_timeout()));
+        let socket = stream.try_clone().map_err(|_| CONNECT_ERROR)?;
+        let mut builder = HttpResponseBuilder::for_method(&self.method);
+        builder.connection_mut().remote_addr = socket_addr;
+        let mut stream: Box<dyn StreamRW> = Box::new(stream);
 
-        let resolved_addrs: Vec<_> = addr
-            .to_socket_addrs()
-            .map_err(|_| "Unable to resolve domain")?
-            .collect();
+        let mut target = self.target();
+        let mut proxy_authorization = None;
+        match proxy {
+            Some(proxy) if proxy.scheme == ProxyScheme::Http && ssl => {
+                if let Some(refused) = proxy.tunnel(&mut stream, &host, port)? {
+                    return Ok(refused);
+                }
+            }
+            Some(proxy) if proxy.scheme == ProxyScheme::Http => {
+                let authority = if port == 80 {
+                    authority(&host, port).trim_end_matches(":80").to_string()
+                } else {
+                    authority(&host, port)
+                };
+                target = format!("http://{}{}", authority, target);
+                proxy_authorization = proxy.authorization();
+            }
+            Some(proxy) => {
+                let addr = match proxy.scheme {
+                    ProxyScheme::Socks5 => self.resolve_addrs(&host, port)?.first().copied(),
+                    _ => None,
+                };
+                proxy.socks5_connect(&mut stream, &host, port, addr)?;
+            }
+            None => {}
+        }
 
-        let socket_addr = resolved_addrs
-            .into_iter()
-            .find(|addr| addr.port() != 0 && !addr.ip().is_unspecified())
-            .ok_or("No valid address found")?;
-        if socket_addr.port() == 443 {
-            ssl = true;
+        if ssl {
+            let (tls_stream, info) = tls::backend()?.connect(&self.tls, &host, stream)?;
+            builder.connection_mut().tls = Some(info);
+            stream = tls_stream;
         }
-        // Connect to server
-        let stream = TcpStream::connect_timeout(&socket_addr, Duration::from_secs(5))
-            .map_err(|_| "Error connecting to server")?;
-        let _ = stream.set_read_timeout(Some(Duration::from_secs(20)));
-        let mut stream: Box<dyn StreamRW> = if ssl {
-            // 1. Crear el conector TLS
-            let connector = TlsConnector::new().map_err(|_| "SLL error")?;
-            let (hostname, _) = self
-                .host
-                .split_once(':')
-                .unwrap_or((&self.host, &self.host));
-            let stream = connector.connect(hostname, stream).map_err(|e| {
-                println!("host: {} \n error: {:?}", hostname, e);
-                "SLL error"
-            })?;
-            Box::new(stream)
+
+        // Sign a copy so the signature covers the final headers and body,
+        // the proxy credentials are added after and stay unsigned
+        let prepared;
+        let missing_host = !self.headers.contains_key("host");
+        let request = if self.signer.is_some() || proxy_authorization.is_some() || missing_host {
+            let mut copy = self.clone();
+            if missing_host {
+                copy.headers.insert("Host", &self.host_header()?);
+            }
+            if let Some(signer) = &self.signer {
+                signer.sign(&mut copy)?;
+            }
+            if let Some(authorization) = &proxy_authorization {
+                copy.headers.insert("Proxy-Authorization", authorization);
+            }
+            prepared = copy;
+            &prepared
         } else {
-            Box::new(stream)
+            self
         };
 
-        let _ = stream.write_all(self.to_string().as_bytes());
+        let _ = stream.write_all(request.head(&target).as_bytes());
+        if request.expects_continue() && !request.body.is_empty() {
+            let _ = stream.flush();
+            let _ = socket.set_read_timeout(Some(CONTINUE_TIMEOUT));
+            let finished = wait_continue(&mut stream, &mut builder)?;
+            let _ = socket.set_read_timeout(Some(self.read_timeout()));
+            if finished {
+                return Ok(builder.get().unwrap());
+            }
+        }
+        let _ = stream.write_all(&request.body);
         let _ = stream.flush();
-        let mut builder = HttpResponseBuilder::new();
         let mut buffer = [0u8; 4096];
         let mut zero_counter = 10;
 
         loop {
             if zero_counter == 0 {
-                return Err("Connexion closed");
+                return Err(CLOSED_ERROR);
             }
             match stream.read(&mut buffer) {
                 Ok(n) => {
@@ -141,23 +279,48 @@ impl HttpRequest {
                         break;
                     }
                 }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    println!("Read timeout");
-                    println!("state: {:?}", builder.state);
-                    println!("Headers: {:?}", builder.headers);
-                    println!("body_len: {:?}", builder.body.len());
-                    break;
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(_) => return Err(READ_ERROR),
+            }
+        }
+
+        builder.get().ok_or(TIMEOUT_ERROR)
+    }
+}
+
+/// Waits for the server to answer an `Expect: 100-continue` request.
+///
+/// Returns `Ok(true)` if the server already sent its final response, in which
+/// case the body must not be sent. A server that stays silent is treated as
+/// a go-ahead once the read times out.
+fn wait_continue(
+    stream: &mut Box<dyn StreamRW>,
+    builder: &mut HttpResponseBuilder,
+) -> Result<bool, &'static str> {
+    let mut buffer = [0u8; 4096];
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) => return Err(CLOSED_ERROR),
+            Ok(n) => {
+                if builder.append(&buffer[..n])? {
+                    return Ok(true);
                 }
-                Err(e) => {
-                    println!("{:?}", e);
-                    println!("state: {:?}", builder.state);
-                    println!("body_len: {:?}", builder.body.len());
-                    return Err("Error reading");
+                if builder
+                    .informational()
+                    .iter()
+                    .any(|r| r.status == HttpStatus::Continue)
+                {
+                    return Ok(false);
                 }
             }
+            Err(ref e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                return Ok(false);
+            }
+            Err(_) => return Err(READ_ERROR),
         }
-
-        Ok(builder.get().unwrap())
     }
 }
 
@@ -182,8 +345,9 @@ mod tests {
     #[test]
     fn test_http_request_arg() {
         let mut request = HttpRequest::new(HttpMethod::Post, "localhost", "/submit");
-        request.args.insert("key".to_string(), "value".to_string());
-        assert_eq!(request.args.get("key"), Some(&"value".to_string()));
+        request.args.push(("key".to_string(), "value".to_string()));
+        request.args.push(("key".to_string(), "other".to_string()));
+        assert_eq!(request.target(), "/submit?key=value&key=other");
     }
 
     #[test]
@@ -215,20 +379,34 @@ mod tests {
         //assert!(request_string.contains("{\"data\":\"test\"}"));
     }
 
-    // #[test]
-    // fn test_http_request_to_string_with_args() {
-    //     let mut request = HttpRequest::new(HttpMethod::POST, "/resource");
-    //     let _ = request
-    //         .header("Content-Type", "application/json")
-    //         .arg("key", "value")
-    //         .body("{\"data\":\"test\"}".to_string())
-    //         .brew("localhost:8080");
-
-    //     let request_string = request.to_string();
-    //     assert!(request_string.contains("POST /resource?key=value HTTP/1.1"));
-    //     assert!(request_string.contains("Content-Type: application/json"));
-    //     assert!(request_string.contains("{\"data\":\"test\"}"));
-    // }
+    #[test]
+    fn test_http_request_header_order() {
+        let mut request = HttpRequest::new(HttpMethod::Get, "localhost", "/");
+        request.headers.insert("Host", "localhost");
+        request.headers.insert("X-Signature", "abc");
+        request.headers.append("Accept", "text/html");
+        request.headers.append("accept", "application/json");
+
+        let request_string = request.to_string();
+        assert!(request_string.starts_with(
+            "GET / HTTP/1.1\r\nHost: localhost\r\nX-Signature: abc\r\nAccept: text/html\r\naccept: application/json\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_http_request_to_string_with_args() {
+        let request = crate::http::Client::post("localhost:8080/resource")
+            .header("Content-Type", "application/json")
+            .query("key", "value")
+            .body("{\"data\":\"test\"}")
+            .build()
+            .unwrap();
+
+        let request_string = request.to_string();
+        assert!(request_string.contains("POST /resource?key=value HTTP/1.1"));
+        assert!(request_string.contains("Content-Type: application/json"));
+        assert!(request_string.contains("{\"data\":\"test\"}"));
+    }
 
     #[test]
     fn test_http_request() {
@@ -236,6 +414,57 @@ mod tests {
         assert!(r.is_ok());
     }
 
+    #[test]
+    fn test_expect_continue() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                head.push_str(&line);
+            }
+            let mut stream = stream;
+            stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").unwrap();
+            let mut body = [0u8; 5];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n")
+                .unwrap();
+            stream.write_all(&body).unwrap();
+            head
+        });
+
+        let mut request =
+            HttpRequest::new(HttpMethod::Put, &format!("127.0.0.1:{}", port), "/upload");
+        request.headers.insert("Expect", "100-continue");
+        request.headers.insert("Content-Length", "5");
+        request.body = b"hello".to_vec();
+        let response = request.brew().unwrap();
+        let head = server.join().unwrap();
+        assert!(head.contains(&format!("Host: 127.0.0.1:{}\r\n", port)));
+        assert_eq!(response.status, HttpStatus::OK);
+        assert_eq!(response.content, b"hello");
+        assert_eq!(response.informational.len(), 1);
+    }
+
+    #[test]
+    fn test_host_header() {
+        let host = |host: &str| HttpRequest::new(HttpMethod::Get, host, "/").host_header();
+        assert_eq!(host("example.com").unwrap(), "example.com");
+        assert_eq!(host("https://example.com:443").unwrap(), "example.com");
+        assert_eq!(host("https://example.com:80").unwrap(), "example.com:80");
+        assert_eq!(host("[::1]:8080").unwrap(), "[::1]:8080");
+        assert_eq!(host("[::1]").unwrap(), "[::1]");
+    }
+
     #[test]
     fn test_http_request_time_out() {
         let r = HttpRequest::new(HttpMethod::Get, "example.org:8080", "/").brew();
@@ -0,0 +1,192 @@
+//! Incremental decoder for `Transfer-Encoding: chunked` bodies.
+//!
+//! Implements the grammar from RFC 9112 §7.1:
+//!
+//! ```text
+//! chunked-body = *chunk last-chunk trailer-section CRLF
+//! chunk        = chunk-size [ chunk-ext ] CRLF chunk-data CRLF
+//! last-chunk   = 1*("0") [ chunk-ext ] CRLF
+//! ```
+//!
+//! Chunk extensions are accepted and ignored, trailer fields are collected
+//! into a separate [`HttpHeaders`]. The decoder never assumes a chunk arrives
+//! in one piece, so input can be split at any byte.
+
+use super::HttpHeaders;
+use std::cmp::min;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChunkState {
+    /// Waiting for a `chunk-size [; ext]` line.
+    Size,
+    /// Reading chunk data, with the number of bytes still expected.
+    Data(usize),
+    /// Waiting for the CRLF that closes a chunk's data.
+    DataEnd,
+    /// Reading trailer fields after the last chunk.
+    Trailers,
+    Done,
+}
+
+/// Decodes a chunked body as it arrives, whatever the chunk boundaries.
+#[derive(Debug)]
+pub struct ChunkedDecoder {
+    state: ChunkState,
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChunkedDecoder {
+    /// Creates a decoder waiting for the first chunk size.
+    pub fn new() -> Self {
+        ChunkedDecoder {
+            state: ChunkState::Size,
+        }
+    }
+
+    /// Returns true once the last chunk and the trailers have been read.
+    pub fn is_done(&self) -> bool {
+        self.state == ChunkState::Done
+    }
+
+    /// Consumes as much of `buffer` as possible.
+    ///
+    /// Decoded data is appended to `body` and trailer fields are inserted into
+    /// `trailers`. Returns `Ok(true)` once the whole chunked body, including the
+    /// trailer section, has been read. Bytes after the end are left in `buffer`.
+    pub fn decode(
+        &mut self,
+        buffer: &mut Vec<u8>,
+        body: &mut Vec<u8>,
+        trailers: &mut HttpHeaders,
+    ) -> Result<bool, &'static str> {
+        loop {
+            match self.state {
+                ChunkState::Size => {
+                    let Some(line) = take_line(buffer)? else {
+                        return Ok(false);
+                    };
+                    let size = parse_chunk_size(&line)?;
+                    self.state = if size == 0 {
+                        ChunkState::Trailers
+                    } else {
+                        ChunkState::Data(size)
+                    };
+                }
+                ChunkState::Data(left) => {
+                    if buffer.is_empty() {
+                        return Ok(false);
+                    }
+                    let to_take = min(left, buffer.len());
+                    body.extend(buffer.drain(..to_take));
+                    self.state = if to_take == left {
+                        ChunkState::DataEnd
+                    } else {
+                        ChunkState::Data(left - to_take)
+                    };
+                }
+                ChunkState::DataEnd => {
+                    if buffer.len() < 2 {
+                        return Ok(false);
+                    }
+                    if &buffer[..2] != b"\r\n" {
+                        return Err("Invalid chunk terminator");
+                    }
+                    buffer.drain(..2);
+                    self.state = ChunkState::Size;
+                }
+                ChunkState::Trailers => {
+                    let Some(line) = take_line(buffer)? else {
+                        return Ok(false);
+                    };
+                    if line.is_empty() {
+                        self.state = ChunkState::Done;
+                        continue;
+                    }
+                    let (k, v) = line.split_once(':').ok_or("Invalid trailer")?;
+                    trailers.append(k.trim(), v.trim());
+                }
+                ChunkState::Done => return Ok(true),
+            }
+        }
+    }
+}
+
+/// Removes the next CRLF-terminated line from `buffer`, without the CRLF.
+fn take_line(buffer: &mut Vec<u8>) -> Result<Option<String>, &'static str> {
+    let Some(pos) = buffer.windows(2).position(|w| w == b"\r\n") else {
+        return Ok(None);
+    };
+    let line = buffer.drain(..pos).collect::<Vec<u8>>();
+    buffer.drain(..2);
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| "Invalid chunk encoding")
+}
+
+/// Parses `chunk-size [ chunk-ext ]`, ignoring any extensions.
+fn parse_chunk_size(line: &str) -> Result<usize, &'static str> {
+    let size = line.split_once(';').map(|(s, _)| s).unwrap_or(line).trim();
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("Invalid chunk size");
+    }
+    usize::from_str_radix(size, 16).map_err(|_| "Invalid chunk size")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(input: &[u8]) -> Result<(Vec<u8>, HttpHeaders), &'static str> {
+        let mut decoder = ChunkedDecoder::new();
+        let mut buffer = input.to_vec();
+        let mut body = Vec::new();
+        let mut trailers = HttpHeaders::new();
+        decoder.decode(&mut buffer, &mut body, &mut trailers)?;
+        assert!(decoder.is_done());
+        Ok((body, trailers))
+    }
+
+    #[test]
+    fn test_chunk_extensions() {
+        let (body, _) =
+            decode_all(b"4;name=value\r\nWiki\r\n5 ; foo\r\npedia\r\n0;last\r\n\r\n").unwrap();
+        assert_eq!(body, b"Wikipedia");
+    }
+
+    #[test]
+    fn test_trailers() {
+        let (body, trailers) =
+            decode_all(b"3\r\nabc\r\n0\r\nExpires: never\r\nX-Checksum: 42\r\n\r\n").unwrap();
+        assert_eq!(body, b"abc");
+        assert_eq!(trailers.get("expires").unwrap(), "never");
+        assert_eq!(trailers.get("x-checksum").unwrap(), "42");
+    }
+
+    #[test]
+    fn test_invalid_chunks() {
+        assert!(decode_all(b"zz\r\nabc\r\n0\r\n\r\n").is_err());
+        assert!(decode_all(b"0x3\r\nabc\r\n0\r\n\r\n").is_err());
+        assert!(decode_all(b"\r\n").is_err());
+        assert!(decode_all(b"3\r\nabcd\r\n0\r\n\r\n").is_err());
+        assert!(decode_all(b"ffffffffffffffffffff\r\n").is_err());
+    }
+
+    #[test]
+    fn test_leaves_trailing_bytes() {
+        let mut decoder = ChunkedDecoder::new();
+        let mut buffer = b"1\r\na\r\n0\r\n\r\nHTTP/1.1".to_vec();
+        let mut body = Vec::new();
+        let mut trailers = HttpHeaders::new();
+        assert!(
+            decoder
+                .decode(&mut buffer, &mut body, &mut trailers)
+                .unwrap()
+        );
+        assert_eq!(buffer, b"HTTP/1.1");
+    }
+}
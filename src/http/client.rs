@@ -0,0 +1,289 @@
+//! A fluent way to build and send requests.
+//!
+//! ```no_run
+//! use sip::Client;
+//! use std::time::Duration;
+//!
+//! let response = Client::get("https://api.example.com/users")
+//!     .header("Accept", "application/json")
+//!     .query("page", "2")
+//!     .bearer("token")
+//!     .timeout(Duration::from_secs(10))
+//!     .send()?;
+//! # Ok::<(), &'static str>(())
+//! ```
+//!
+//! Errors in the builder, like a bad URL, are kept and returned by
+//! [`RequestBuilder::build`] or [`RequestBuilder::send`].
+
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use super::signing::uri_encode;
+use super::{HttpMethod, HttpRequest, HttpResponse, RetryPolicy};
+
+/// Starts requests, each method returns a [`RequestBuilder`].
+pub struct Client;
+
+impl Client {
+    /// Starts a request with any method.
+    pub fn request(method: HttpMethod, url: &str) -> RequestBuilder {
+        RequestBuilder::new(method, url)
+    }
+
+    /// Starts a `GET` request.
+    pub fn get(url: &str) -> RequestBuilder {
+        Client::request(HttpMethod::Get, url)
+    }
+
+    /// Starts a `POST` request.
+    pub fn post(url: &str) -> RequestBuilder {
+        Client::request(HttpMethod::Post, url)
+    }
+
+    /// Starts a `PUT` request.
+    pub fn put(url: &str) -> RequestBuilder {
+        Client::request(HttpMethod::Put, url)
+    }
+
+    /// Starts a `PATCH` request.
+    pub fn patch(url: &str) -> RequestBuilder {
+        Client::request(HttpMethod::Patch, url)
+    }
+
+    /// Starts a `DELETE` request.
+    pub fn delete(url: &str) -> RequestBuilder {
+        Client::request(HttpMethod::Delete, url)
+    }
+
+    /// Starts a `HEAD` request.
+    pub fn head(url: &str) -> RequestBuilder {
+        Client::request(HttpMethod::Head, url)
+    }
+}
+
+/// An [`HttpRequest`] being built.
+#[derive(Debug)]
+pub struct RequestBuilder {
+    request: HttpRequest,
+    error: Option<&'static str>,
+}
+
+impl RequestBuilder {
+    /// Starts a request to `url`, like `https://host:port/path?query`.
+    ///
+    /// Without a scheme the URL is sent as plain `http`.
+    pub fn new(method: HttpMethod, url: &str) -> Self {
+        let url = url.split('#').next().unwrap_or_default();
+        let (scheme, rest) = match url.split_once("://") {
+            Some((scheme, rest)) => (scheme.to_lowercase(), rest),
+            None => ("http".to_string(), url),
+        };
+        let end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(end);
+        let path = match path {
+            "" => "/".to_string(),
+            p if p.starts_with('?') => format!("/{}", p),
+            p => p.to_string(),
+        };
+        let mut request = HttpRequest::new(method, &format!("{}://{}", scheme, authority), &path);
+        let error = match scheme.as_str() {
+            "http" | "https" => request.endpoint().err(),
+            _ => Some("Unsupported URL scheme"),
+        };
+        if error.is_none() {
+            request.headers.insert("Host", authority);
+        }
+        RequestBuilder { request, error }
+    }
+
+    /// Sets a header, replacing any previous value.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.request.headers.insert(name, value);
+        self
+    }
+
+    /// Adds a query parameter, percent-encoded, after the previous ones.
+    /// Repeating a name sends it several times.
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.request
+            .args
+            .push((uri_encode(name), uri_encode(value)));
+        self
+    }
+
+    /// Sets the body and its `Content-Length`.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.request.body = body.into();
+        self
+    }
+
+    /// Serializes `value` as the JSON body.
+    #[cfg(feature = "serde")]
+    pub fn json<T: serde::Serialize + ?Sized>(mut self, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => {
+                self.request.body = body;
+                self.request
+                    .headers
+                    .insert("Content-Type", "application/json");
+            }
+            Err(_) => self.error = self.error.or(Some("Error serializing JSON body")),
+        }
+        self
+    }
+
+    /// Sends `Authorization: Bearer <token>`.
+    pub fn bearer(self, token: &str) -> Self {
+        self.header("Authorization", &format!("Bearer {}", token))
+    }
+
+    /// Sends `Authorization: Basic` with `user` and `password`.
+    pub fn basic_auth(self, user: &str, password: &str) -> Self {
+        let credentials = STANDARD.encode(format!("{}:{}", user, password));
+        self.header("Authorization", &format!("Basic {}", credentials))
+    }
+
+    /// Limits connecting and each read to `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.request.timeout = Some(timeout);
+        self
+    }
+
+    /// Retries transient failures with `policy`.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.request.retry = Some(policy);
+        self
+    }
+
+    /// Returns the request, ready to `brew`.
+    pub fn build(mut self) -> Result<HttpRequest, &'static str> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if !self.request.body.is_empty() {
+            let length = self.request.body.len().to_string();
+            self.request.headers.insert("Content-Length", &length);
+        }
+        Ok(self.request)
+    }
+
+    /// Builds the request and sends it with `brew`.
+    pub fn send(self) -> Result<HttpResponse, &'static str> {
+        self.build()?.brew()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpStatus;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_builder() {
+        let request = Client::post("https://api.example.com:8443/users?active=1#top")
+            .header("Accept", "application/json")
+            .query("name", "tea pot")
+            .query("tag", "b")
+            .query("tag", "a")
+            .bearer("abc")
+            .body("hi")
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.endpoint().unwrap(),
+            (true, "api.example.com".into(), 8443)
+        );
+        assert_eq!(
+            request.target(),
+            "/users?active=1&name=tea%20pot&tag=b&tag=a"
+        );
+        assert_eq!(request.headers.get("host").unwrap(), "api.example.com:8443");
+        assert_eq!(request.headers.get("authorization").unwrap(), "Bearer abc");
+        assert_eq!(request.headers.get("content-length").unwrap(), "2");
+
+        let request = Client::get("localhost?x=1").build().unwrap();
+        assert_eq!(request.target(), "/?x=1");
+        assert_eq!(request.endpoint().unwrap(), (false, "localhost".into(), 80));
+
+        assert!(Client::get("ftp://example.com").build().is_err());
+        assert!(Client::get("http://example.com:port/").send().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let request = Client::put("http://localhost/items/1")
+            .json(&serde_json::json!({"name": "tea"}))
+            .build()
+            .unwrap();
+        assert_eq!(request.body, br#"{"name":"tea"}"#);
+        assert_eq!(
+            request.headers.get("content-type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(request.headers.get("content-length").unwrap(), "14");
+    }
+
+    #[test]
+    fn test_send() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        let (finished, done) = mpsc::channel::<()>();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut sent = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !sent.ends_with(b"x=1") {
+                let n = stream.read(&mut buffer).unwrap();
+                sent.extend_from_slice(&buffer[..n]);
+            }
+            tx.send(String::from_utf8(sent).unwrap()).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+
+            // Keep the connection open, the reply must end at the headers
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut sent = Vec::new();
+            while !sent.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buffer).unwrap();
+                sent.extend_from_slice(&buffer[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1234\r\n\r\n")
+                .unwrap();
+            tx.send(String::from_utf8(sent).unwrap()).unwrap();
+            let _ = done.recv();
+        });
+
+        let response = Client::post(&format!("http://127.0.0.1:{}/items", port))
+            .basic_auth("user", "pass")
+            .body("x=1")
+            .timeout(Duration::from_secs(2))
+            .send()
+            .unwrap();
+        assert_eq!(response.status, HttpStatus::Created);
+        let sent = rx.recv().unwrap();
+        assert!(sent.starts_with("POST /items HTTP/1.1\r\n"));
+        assert!(sent.contains(&format!("Host: 127.0.0.1:{}\r\n", port)));
+        assert!(sent.contains("Authorization: Basic dXNlcjpwYXNz\r\n"));
+        assert!(sent.ends_with("\r\n\r\nx=1"));
+
+        let response = Client::head(&format!("http://127.0.0.1:{}/items", port))
+            .timeout(Duration::from_secs(2))
+            .send()
+            .unwrap();
+        assert_eq!(response.status, HttpStatus::OK);
+        assert_eq!(response.content_length(), Some(1234));
+        assert!(response.content.is_empty());
+        assert!(rx.recv().unwrap().starts_with("HEAD /items HTTP/1.1\r\n"));
+        finished.send(()).unwrap();
+    }
+}
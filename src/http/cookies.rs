@@ -0,0 +1,664 @@
+//! Cookie jar following the storage and retrieval rules of RFC 6265.
+//!
+//! The jar is filled from `Set-Cookie` response headers and produces the
+//! `Cookie` header for later requests. It can be persisted as a Netscape
+//! `cookies.txt` file (the format used by curl and wget) or as a JSON array.
+//!
+//! There is no public suffix list, so a server can still set a cookie for
+//! a shared parent domain such as `co.uk`.
+
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{HttpRequest, HttpResponse};
+
+/// A single stored cookie.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cookie {
+    /// The cookie name.
+    pub name: String,
+    /// The cookie value, as sent by the server.
+    pub value: String,
+    /// Lowercase domain, without a leading dot.
+    pub domain: String,
+    /// Set when the cookie had no `Domain` attribute and only matches the exact host.
+    pub host_only: bool,
+    /// Path the cookie is sent for, and below it.
+    pub path: String,
+    /// Expiry as seconds since the unix epoch, `None` for session cookies.
+    pub expires: Option<u64>,
+    /// Only sent over `https`.
+    pub secure: bool,
+    /// Not meant for scripts, kept for the cookie files.
+    pub http_only: bool,
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header value received from `host` for `path`.
+    ///
+    /// Returns `None` if the cookie must be ignored, e.g. because its
+    /// `Domain` doesn't match the host that sent it.
+    pub fn parse(header: &str, host: &str, path: &str, now: u64) -> Option<Cookie> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let host = host.to_lowercase();
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(path),
+            expires: None,
+            secure: false,
+            http_only: false,
+        };
+        let mut max_age: Option<i64> = None;
+        let mut expires: Option<u64> = None;
+
+        for attribute in parts {
+            let (k, v) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let v = v.trim();
+            match k.trim().to_lowercase().as_str() {
+                "expires" => expires = parse_cookie_date(v).or(expires),
+                "max-age" => max_age = v.parse().ok().or(max_age),
+                "domain" => {
+                    let domain = v.trim_start_matches('.').to_lowercase();
+                    if !domain.is_empty() {
+                        cookie.domain = domain;
+                        cookie.host_only = false;
+                    }
+                }
+                "path" if v.starts_with('/') => cookie.path = v.to_string(),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                _ => {}
+            }
+        }
+
+        if !cookie.host_only && !domain_match(&host, &cookie.domain) {
+            return None;
+        }
+        // Max-Age wins over Expires
+        cookie.expires = match max_age {
+            Some(age) if age <= 0 => Some(0),
+            Some(age) => Some(now.saturating_add(age as u64)),
+            None => expires,
+        };
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, host: &str, path: &str, secure: bool) -> bool {
+        let domain_ok = if self.host_only {
+            self.domain == host
+        } else {
+            domain_match(host, &self.domain)
+        };
+        domain_ok && path_match(path, &self.path) && (secure || !self.secure)
+    }
+}
+
+/// Stores cookies across requests.
+#[derive(Debug, Default, Clone)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    /// Creates an empty jar.
+    pub fn new() -> Self {
+        CookieJar {
+            cookies: Vec::new(),
+        }
+    }
+
+    /// Iterates over the stored cookies.
+    pub fn iter(&self) -> std::slice::Iter<'_, Cookie> {
+        self.cookies.iter()
+    }
+
+    /// Number of stored cookies.
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    /// Returns true if the jar holds no cookie.
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    /// Adds a cookie, replacing the one with the same name, domain and path.
+    ///
+    /// An already expired cookie removes the stored one instead.
+    pub fn insert(&mut self, cookie: Cookie) {
+        let now = unix_now();
+        let existing = self.cookies.iter().position(|c| {
+            c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+        });
+        match (existing, cookie.is_expired(now)) {
+            (Some(pos), true) => {
+                self.cookies.remove(pos);
+            }
+            (Some(pos), false) => self.cookies[pos] = cookie,
+            (None, true) => {}
+            (None, false) => self.cookies.push(cookie),
+        }
+    }
+
+    /// Stores every `Set-Cookie` of `response`, which answered `request`.
+    pub fn store(&mut self, request: &HttpRequest, response: &HttpResponse) {
+        let now = unix_now();
+        let (host, path, _) = request_origin(request);
+        for header in response.headers.get_all("set-cookie") {
+            if let Some(cookie) = Cookie::parse(header, &host, &path, now) {
+                self.insert(cookie);
+            }
+        }
+    }
+
+    /// Returns the `Cookie` header value for a request, if any cookie matches.
+    pub fn header_for(&self, host: &str, path: &str, secure: bool) -> Option<String> {
+        let now = unix_now();
+        let host = host.to_lowercase();
+        let mut matching: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|c| !c.is_expired(now) && c.matches(&host, path, secure))
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // Longer paths first, otherwise in creation order
+        matching.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        let pairs: Vec<String> = matching
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    /// Adds the matching cookies to the `Cookie` header of `request`.
+    ///
+    /// Cookies already in the header are kept and win over the jar's.
+    pub fn apply(&self, request: &mut HttpRequest) {
+        let (host, path, secure) = request_origin(request);
+        let Some(cookies) = self.header_for(&host, &path, secure) else {
+            return;
+        };
+        let value = match request.headers.get("cookie") {
+            Some(existing) => {
+                let names: Vec<&str> = existing
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .map(|(name, _)| name.trim())
+                    .collect();
+                let added: Vec<&str> = cookies
+                    .split("; ")
+                    .filter(|pair| {
+                        !pair
+                            .split_once('=')
+                            .is_some_and(|(name, _)| names.contains(&name))
+                    })
+                    .collect();
+                if added.is_empty() {
+                    return;
+                }
+                format!("{}; {}", existing, added.join("; "))
+            }
+            None => cookies,
+        };
+        request.headers.insert("Cookie", &value);
+    }
+
+    /// Loads a jar from disk, picking the format from the file extension.
+    ///
+    /// A missing file gives an empty jar.
+    pub fn load(path: &str) -> Result<CookieJar, &'static str> {
+        if !Path::new(path).exists() {
+            return Ok(CookieJar::new());
+        }
+        let content = fs::read_to_string(path).map_err(|_| "Error reading cookie jar")?;
+        if is_json(path) {
+            CookieJar::from_json(&content)
+        } else {
+            Ok(CookieJar::from_netscape(&content))
+        }
+    }
+
+    /// Saves the jar to disk, dropping expired cookies.
+    pub fn save(&self, path: &str) -> Result<(), &'static str> {
+        let content = if is_json(path) {
+            self.to_json()?
+        } else {
+            self.to_netscape()
+        };
+        fs::write(path, content).map_err(|_| "Error writing cookie jar")
+    }
+
+    /// Parses a Netscape `cookies.txt` file. Malformed lines are skipped.
+    pub fn from_netscape(content: &str) -> CookieJar {
+        let mut jar = CookieJar::new();
+        for line in content.lines() {
+            let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => (line, true),
+                None => (line, false),
+            };
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 7 {
+                continue;
+            }
+            let Ok(expires) = fields[4].parse::<u64>() else {
+                continue;
+            };
+            jar.insert(Cookie {
+                name: fields[5].to_string(),
+                value: fields[6].to_string(),
+                domain: fields[0].trim_start_matches('.').to_lowercase(),
+                host_only: fields[1] != "TRUE",
+                path: fields[2].to_string(),
+                expires: if expires == 0 { None } else { Some(expires) },
+                secure: fields[3] == "TRUE",
+                http_only,
+            });
+        }
+        jar
+    }
+
+    /// Serializes the jar as a Netscape `cookies.txt` file.
+    ///
+    /// Session cookies are written with an expiry of 0, like curl does.
+    pub fn to_netscape(&self) -> String {
+        let now = unix_now();
+        let mut content = String::from("# Netscape HTTP Cookie File\n");
+        for cookie in self.cookies.iter().filter(|c| !c.is_expired(now)) {
+            let prefix = if cookie.http_only { "#HttpOnly_" } else { "" };
+            let domain = if cookie.host_only {
+                cookie.domain.clone()
+            } else {
+                format!(".{}", cookie.domain)
+            };
+            content.push_str(&format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                prefix,
+                domain,
+                if cookie.host_only { "FALSE" } else { "TRUE" },
+                cookie.path,
+                if cookie.secure { "TRUE" } else { "FALSE" },
+                cookie.expires.unwrap_or(0),
+                cookie.name,
+                cookie.value,
+            ));
+        }
+        content
+    }
+
+    /// Reads a jar written by [`to_json`](Self::to_json).
+    pub fn from_json(content: &str) -> Result<CookieJar, &'static str> {
+        use serde_json::Value;
+        let value: Value = serde_json::from_str(content).map_err(|_| "Invalid cookie jar")?;
+        let entries = value.as_array().ok_or("Invalid cookie jar")?;
+        let mut jar = CookieJar::new();
+        for entry in entries {
+            let text = |k: &str| entry.get(k).and_then(Value::as_str).map(str::to_string);
+            let flag = |k: &str| entry.get(k).and_then(Value::as_bool).unwrap_or(false);
+            jar.insert(Cookie {
+                name: text("name").ok_or("Invalid cookie jar")?,
+                value: text("value").unwrap_or_default(),
+                domain: text("domain").ok_or("Invalid cookie jar")?,
+                host_only: flag("host_only"),
+                path: text("path").unwrap_or_else(|| "/".to_string()),
+                expires: entry.get("expires").and_then(Value::as_u64),
+                secure: flag("secure"),
+                http_only: flag("http_only"),
+            });
+        }
+        Ok(jar)
+    }
+
+    /// Writes the cookies that haven't expired as a JSON array.
+    pub fn to_json(&self) -> Result<String, &'static str> {
+        let now = unix_now();
+        let entries: Vec<serde_json::Value> = self
+            .cookies
+            .iter()
+            .filter(|c| !c.is_expired(now))
+            .map(|c| {
+                serde_json::json!({
+                    "name": c.name,
+                    "value": c.value,
+                    "domain": c.domain,
+                    "host_only": c.host_only,
+                    "path": c.path,
+                    "expires": c.expires,
+                    "secure": c.secure,
+                    "http_only": c.http_only,
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&entries).map_err(|_| "Error serializing cookie jar")
+    }
+}
+
+fn is_json(path: &str) -> bool {
+    path.to_lowercase().ends_with(".json")
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Returns the lowercase host, the path and whether the request uses TLS.
+fn request_origin(request: &HttpRequest) -> (String, String, bool) {
+    let mut secure = request.ssl;
+    let mut host = request.host.as_str();
+    if let Some(stripped) = host.strip_prefix("https://") {
+        host = stripped;
+        secure = true;
+    }
+    let host = host.strip_prefix("http://").unwrap_or(host);
+    let host = host.split('/').next().unwrap_or(host);
+    let host = if let Some(rest) = host.strip_prefix('[') {
+        // IPv6 literal
+        rest.split(']').next().unwrap_or(rest)
+    } else {
+        host.split(':').next().unwrap_or(host)
+    };
+    let path = request.path.split('?').next().unwrap_or("/");
+    let path = if path.is_empty() { "/" } else { path };
+    (host.to_lowercase(), path.to_string(), secure)
+}
+
+/// Default cookie path for a request path (RFC 6265 §5.1.4).
+fn default_path(path: &str) -> String {
+    let path = path.split('?').next().unwrap_or(path);
+    if !path.starts_with('/') {
+        return "/".to_string();
+    }
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(pos) => path[..pos].to_string(),
+    }
+}
+
+/// Domain matching from RFC 6265 §5.1.3.
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    host.ends_with(domain)
+        && host[..host.len() - domain.len()].ends_with('.')
+        && host.parse::<std::net::IpAddr>().is_err()
+}
+
+/// Path matching from RFC 6265 §5.1.4.
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// Parses a cookie date with the lenient algorithm of RFC 6265 §5.1.1.
+pub(crate) fn parse_cookie_date(date: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let is_delimiter = |c: char| {
+        c == '\t'
+            || (' '..='/').contains(&c)
+            || (';'..='@').contains(&c)
+            || ('['..='`').contains(&c)
+            || ('{'..='~').contains(&c)
+    };
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+
+    for token in date.split(is_delimiter).filter(|t| !t.is_empty()) {
+        if time.is_none() {
+            let parts: Vec<&str> = token.splitn(3, ':').collect();
+            if parts.len() == 3 {
+                let numbers: Vec<Option<u64>> =
+                    parts.iter().map(|p| leading_digits(p, 1, 2)).collect();
+                if let [Some(h), Some(m), Some(s)] = numbers[..] {
+                    time = Some((h, m, s));
+                    continue;
+                }
+            }
+        }
+        if day.is_none()
+            && let Some(d) = leading_digits(token, 1, 2)
+        {
+            day = Some(d);
+            continue;
+        }
+        // Compare bytes, the token may start with a multi-byte character
+        if month.is_none()
+            && let Some(prefix) = token.as_bytes().get(..3)
+            && let Some(m) = MONTHS
+                .iter()
+                .position(|m| prefix.eq_ignore_ascii_case(m.as_bytes()))
+        {
+            month = Some(m as u64 + 1);
+            continue;
+        }
+        if year.is_none()
+            && let Some(y) = leading_digits(token, 2, 4)
+        {
+            year = Some(y);
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let (day, month, mut year) = (day?, month?, year?);
+    if (70..=99).contains(&year) {
+        year += 1900;
+    } else if year <= 69 {
+        year += 2000;
+    }
+    if !(1..=31).contains(&day) || year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day)?;
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Parses `min..=max` leading digits, which must not be followed by another digit.
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<u64> {
+    let digits = token.bytes().take_while(|b| b.is_ascii_digit()).count();
+    if digits < min || digits > max {
+        return None;
+    }
+    token[..digits].parse().ok()
+}
+
+/// Days since the unix epoch, or `None` for dates before it.
+fn days_from_civil(year: u64, month: u64, day: u64) -> Option<u64> {
+    let year = if month <= 2 { year - 1 } else { year } as i64;
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    u64::try_from(era * 146097 + doe - 719468).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attributes() {
+        let cookie = Cookie::parse(
+            "sid=abc; Domain=.Example.com; Path=/api; Secure; HttpOnly; Max-Age=60",
+            "www.example.com",
+            "/login",
+            1000,
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "sid");
+        assert_eq!(cookie.value, "abc");
+        assert_eq!(cookie.domain, "example.com");
+        assert!(!cookie.host_only);
+        assert_eq!(cookie.path, "/api");
+        assert_eq!(cookie.expires, Some(1060));
+        assert!(cookie.secure && cookie.http_only);
+
+        let cookie = Cookie::parse("a=1", "example.com", "/docs/page", 0).unwrap();
+        assert!(cookie.host_only);
+        assert_eq!(cookie.path, "/docs");
+        assert_eq!(cookie.expires, None);
+    }
+
+    #[test]
+    fn test_rejects_foreign_domain() {
+        assert!(Cookie::parse("a=1; Domain=other.com", "example.com", "/", 0).is_none());
+        assert!(Cookie::parse("a=1; Domain=ample.com", "example.com", "/", 0).is_none());
+        assert!(Cookie::parse("=1", "example.com", "/", 0).is_none());
+    }
+
+    #[test]
+    fn test_matching() {
+        let mut jar = CookieJar::new();
+        let now = unix_now();
+        for header in [
+            "a=1; Path=/",
+            "b=2; Path=/api",
+            "c=3; Secure",
+            "d=4; Domain=example.com",
+        ] {
+            jar.insert(Cookie::parse(header, "example.com", "/", now).unwrap());
+        }
+        assert_eq!(
+            jar.header_for("example.com", "/api/users", false).unwrap(),
+            "b=2; a=1; d=4"
+        );
+        assert_eq!(
+            jar.header_for("example.com", "/apiary", true).unwrap(),
+            "a=1; c=3; d=4"
+        );
+        assert_eq!(jar.header_for("www.example.com", "/", true).unwrap(), "d=4");
+        assert!(jar.header_for("other.com", "/", true).is_none());
+    }
+
+    #[test]
+    fn test_expiry_removes_cookie() {
+        let mut jar = CookieJar::new();
+        jar.insert(Cookie::parse("a=1", "example.com", "/", 0).unwrap());
+        assert_eq!(jar.len(), 1);
+        jar.insert(Cookie::parse("a=; Max-Age=0", "example.com", "/", 0).unwrap());
+        assert!(jar.is_empty());
+        jar.insert(
+            Cookie::parse(
+                "b=1; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+                "example.com",
+                "/",
+                0,
+            )
+            .unwrap(),
+        );
+        assert!(jar.is_empty());
+    }
+
+    #[test]
+    fn test_cookie_dates() {
+        assert_eq!(
+            parse_cookie_date("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(1445412480)
+        );
+        assert_eq!(
+            parse_cookie_date("Wednesday, 21-Oct-15 07:28:00 GMT"),
+            Some(1445412480)
+        );
+        assert_eq!(
+            parse_cookie_date("Wed Oct 21 07:28:00 2015"),
+            Some(1445412480)
+        );
+        assert_eq!(parse_cookie_date("not a date"), None);
+        assert_eq!(parse_cookie_date("Wed, 32 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_cookie_date("éé"), None);
+        assert_eq!(parse_cookie_date("21 éOct 2015 07:28:00"), None);
+    }
+
+    #[test]
+    fn test_netscape_round_trip() {
+        let mut jar = CookieJar::new();
+        jar.insert(
+            Cookie::parse(
+                "sid=abc; Domain=example.com; HttpOnly; Secure; Max-Age=3600",
+                "example.com",
+                "/",
+                unix_now(),
+            )
+            .unwrap(),
+        );
+        jar.insert(Cookie::parse("theme=dark", "example.com", "/", 0).unwrap());
+        let text = jar.to_netscape();
+        assert!(text.contains("#HttpOnly_.example.com\tTRUE\t/\tTRUE\t"));
+        assert!(text.contains("example.com\tFALSE\t/\tFALSE\t0\ttheme\tdark"));
+        let loaded = CookieJar::from_netscape(&text);
+        assert_eq!(loaded.cookies, jar.cookies);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut jar = CookieJar::new();
+        jar.insert(Cookie::parse("a=1; Path=/x; Secure", "example.com", "/", 0).unwrap());
+        let loaded = CookieJar::from_json(&jar.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.cookies, jar.cookies);
+    }
+
+    #[test]
+    fn test_store_and_apply() {
+        let mut response = crate::http::response::HttpResponseBuilder::new();
+        response
+            .append(b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2; Secure\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        let response = response.get().unwrap();
+        let request = HttpRequest::parse("GET http://example.com:8080/login".to_string()).unwrap();
+        let mut jar = CookieJar::new();
+        jar.store(&request, &response);
+        assert_eq!(jar.len(), 2);
+
+        let mut next = HttpRequest::parse("GET http://example.com/me".to_string()).unwrap();
+        jar.apply(&mut next);
+        assert_eq!(next.headers.get("cookie").unwrap(), "a=1");
+
+        // A second jar adds to the header instead of replacing it
+        let mut other = CookieJar::new();
+        other.insert(Cookie::parse("a=2", "example.com", "/", 0).unwrap());
+        other.insert(Cookie::parse("c=3", "example.com", "/", 0).unwrap());
+        other.apply(&mut next);
+        assert_eq!(next.headers.get("cookie").unwrap(), "a=1; c=3");
+    }
+
+    #[test]
+    fn test_non_ascii_expires() {
+        let mut response = crate::http::response::HttpResponseBuilder::new();
+        response
+            .append(
+                "HTTP/1.1 200 OK\r\nSet-Cookie: a=1; Expires=éé\r\nContent-Length: 0\r\n\r\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+        let request = HttpRequest::parse("GET http://example.com/".to_string()).unwrap();
+        let mut jar = CookieJar::new();
+        jar.store(&request, &response.get().unwrap());
+        assert_eq!(jar.len(), 1);
+        assert_eq!(jar.iter().next().unwrap().expires, None);
+    }
+}
@@ -0,0 +1,174 @@
+//! Content-Encoding support for response bodies.
+//!
+//! Each coding is behind its own cargo feature (`gzip`, `deflate`, `br`,
+//! `zstd`). Only the enabled ones are advertised in `Accept-Encoding`, so a
+//! well behaved server never sends something we can't decode.
+
+#[cfg(any(feature = "gzip", feature = "deflate", feature = "br"))]
+use std::io::Read;
+
+use super::HttpResponse;
+
+/// Codings compiled into this build, in order of preference.
+const SUPPORTED: &[&str] = &[
+    #[cfg(feature = "zstd")]
+    "zstd",
+    #[cfg(feature = "br")]
+    "br",
+    #[cfg(feature = "gzip")]
+    "gzip",
+    #[cfg(feature = "deflate")]
+    "deflate",
+];
+
+/// Returns the value for the `Accept-Encoding` header, or `None` if no
+/// coding feature is enabled.
+pub fn accept_encoding() -> Option<String> {
+    if SUPPORTED.is_empty() {
+        None
+    } else {
+        Some(SUPPORTED.join(", "))
+    }
+}
+
+/// Decodes `body` according to a `Content-Encoding` header value.
+///
+/// Codings are listed in the order they were applied, so they are undone
+/// from last to first.
+pub fn decode(content_encoding: &str, body: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut body = body.to_vec();
+    for coding in content_encoding.rsplit(',') {
+        let coding = coding.trim().to_lowercase();
+        body = decode_one(&coding, body)?;
+    }
+    Ok(body)
+}
+
+fn decode_one(coding: &str, body: Vec<u8>) -> Result<Vec<u8>, &'static str> {
+    match coding {
+        "" | "identity" => Ok(body),
+        #[cfg(feature = "gzip")]
+        "gzip" | "x-gzip" => read_all(
+            flate2::read::MultiGzDecoder::new(body.as_slice()),
+            "Invalid gzip body",
+        ),
+        // Some servers send raw deflate instead of the zlib wrapper
+        #[cfg(feature = "deflate")]
+        "deflate" => read_all(
+            flate2::read::ZlibDecoder::new(body.as_slice()),
+            "Invalid deflate body",
+        )
+        .or_else(|_| {
+            read_all(
+                flate2::read::DeflateDecoder::new(body.as_slice()),
+                "Invalid deflate body",
+            )
+        }),
+        #[cfg(feature = "br")]
+        "br" => read_all(
+            brotli::Decompressor::new(body.as_slice(), 4096),
+            "Invalid brotli body",
+        ),
+        #[cfg(feature = "zstd")]
+        "zstd" => zstd::stream::decode_all(body.as_slice()).map_err(|_| "Invalid zstd body"),
+        _ => Err("Unsupported content encoding"),
+    }
+}
+
+#[cfg(any(feature = "gzip", feature = "deflate", feature = "br"))]
+fn read_all(mut reader: impl Read, error: &'static str) -> Result<Vec<u8>, &'static str> {
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).map_err(|_| error)?;
+    Ok(out)
+}
+
+impl HttpResponse {
+    /// Decodes the body in place using its `Content-Encoding`.
+    ///
+    /// On success the `Content-Encoding` and `Content-Length` headers are
+    /// removed, since they no longer describe `content`.
+    pub fn decompress(&mut self) -> Result<(), &'static str> {
+        let Some(content_encoding) = self.headers.get("content-encoding") else {
+            return Ok(());
+        };
+        self.content = decode(content_encoding, &self.content)?;
+        self.headers.remove("content-encoding");
+        self.headers.remove("content-length");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(any(
+        feature = "gzip",
+        feature = "deflate",
+        feature = "br",
+        feature = "zstd"
+    ))]
+    use std::io::Write;
+
+    #[test]
+    fn test_identity() {
+        assert_eq!(decode("identity", b"tea").unwrap(), b"tea");
+        assert!(decode("compress", b"tea").is_err());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"hello gzip").unwrap();
+        let body = encoder.finish().unwrap();
+        assert_eq!(decode("gzip", &body).unwrap(), b"hello gzip");
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn test_deflate() {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"hello zlib").unwrap();
+        let body = encoder.finish().unwrap();
+        assert_eq!(decode("deflate", &body).unwrap(), b"hello zlib");
+
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"hello deflate").unwrap();
+        let body = encoder.finish().unwrap();
+        assert_eq!(decode("deflate", &body).unwrap(), b"hello deflate");
+    }
+
+    #[cfg(feature = "br")]
+    #[test]
+    fn test_brotli() {
+        let mut body = Vec::new();
+        {
+            let mut encoder = brotli::CompressorWriter::new(&mut body, 4096, 5, 22);
+            encoder.write_all(b"hello brotli").unwrap();
+        }
+        assert_eq!(decode("br", &body).unwrap(), b"hello brotli");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        let body = zstd::stream::encode_all(&b"hello zstd"[..], 3).unwrap();
+        assert_eq!(decode("zstd", &body).unwrap(), b"hello zstd");
+    }
+
+    #[cfg(all(feature = "gzip", feature = "br"))]
+    #[test]
+    fn test_stacked_codings() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"layers").unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let mut body = Vec::new();
+        {
+            let mut encoder = brotli::CompressorWriter::new(&mut body, 4096, 5, 22);
+            encoder.write_all(&gzipped).unwrap();
+        }
+        assert_eq!(decode("gzip, br", &body).unwrap(), b"layers");
+    }
+}
@@ -1,9 +1,9 @@
-use std::collections::hash_map::Entry;
-use std::collections::{HashMap, hash_map};
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
+use std::{slice, vec};
 
+/// A header name, compared and hashed without regard to ASCII case.
 #[derive(Debug, Clone)]
 pub struct CaseInsensitiveString(String);
 
@@ -35,56 +35,109 @@ impl Deref for CaseInsensitiveString {
     }
 }
 
+type Header = (CaseInsensitiveString, String);
+type Iter<'a> = std::iter::Map<
+    slice::Iter<'a, Header>,
+    fn(&'a Header) -> (&'a CaseInsensitiveString, &'a String),
+>;
+type IterMut<'a> = std::iter::Map<
+    slice::IterMut<'a, Header>,
+    fn(&'a mut Header) -> (&'a CaseInsensitiveString, &'a mut String),
+>;
+
+/// Ordered, case-insensitive multimap of header fields.
+///
+/// Fields keep the order and the case they were added with, so repeated
+/// fields like `Set-Cookie` survive and requests go on the wire exactly as
+/// they were built.
 #[derive(Debug, Default, Clone)]
-pub struct HttpHeaders(HashMap<CaseInsensitiveString, String>);
+pub struct HttpHeaders(Vec<Header>);
 
 impl HttpHeaders {
+    /// Creates an empty header map.
     pub fn new() -> Self {
-        HttpHeaders(HashMap::new())
+        HttpHeaders(Vec::new())
     }
 
+    /// Sets a field, replacing any existing values for it.
+    ///
+    /// The field keeps the position of its first occurrence.
     pub fn insert(&mut self, key: &str, value: &str) {
-        // Ejemplo: forzar keys a lowercase
+        let key = CaseInsensitiveString(key.to_string());
+        match self.0.iter().position(|(k, _)| *k == key) {
+            Some(pos) => {
+                self.0[pos].1 = value.to_string();
+                let mut i = pos + 1;
+                while i < self.0.len() {
+                    if self.0[i].0 == key {
+                        self.0.remove(i);
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            None => self.0.push((key, value.to_string())),
+        }
+    }
+
+    /// Adds a field at the end, keeping any existing values for it.
+    pub fn append(&mut self, key: &str, value: &str) {
         self.0
-            .insert(CaseInsensitiveString(key.to_string()), value.to_string());
+            .push((CaseInsensitiveString(key.to_string()), value.to_string()));
     }
 
+    /// Returns true if there is at least one value for `key`.
     pub fn contains_key(&self, key: &str) -> bool {
-        self.0.contains_key(&CaseInsensitiveString(key.to_string()))
+        self.get(key).is_some()
     }
 
+    /// Returns the first value for `key`.
     pub fn get(&self, key: &str) -> Option<&String> {
-        self.0.get(&CaseInsensitiveString(key.to_string()))
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+
+    /// Returns every value for `key`, in order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.0
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
     }
 
+    /// Returns a copy of the first value for `key`.
     pub fn get_owned(&self, key: &str) -> Option<String> {
-        self.0.get(&CaseInsensitiveString(key.to_string())).cloned()
+        self.get(key).cloned()
     }
 
+    /// Number of fields, counting repeated ones separately.
     pub fn len(&self) -> usize {
         self.0.len()
     }
 
+    /// Returns true if there are no fields.
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 
-    pub fn entry(&mut self, key: &str) -> Entry<'_, CaseInsensitiveString, String> {
-        self.0.entry(CaseInsensitiveString(key.to_string()))
-    }
-
+    /// Removes every value for `key`, returning the first one.
     pub fn remove(&mut self, key: &str) -> Option<String> {
-        self.0.remove(&CaseInsensitiveString(key.to_string()))
+        let first = self.get_owned(key);
+        self.0.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+        first
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, CaseInsensitiveString, String> {
-        self.0.iter()
+    /// Iterates over the fields in order, repeated ones included.
+    pub fn iter(&self) -> Iter<'_> {
+        self.0.iter().map(|(k, v)| (k, v))
     }
 }
 
 impl IntoIterator for HttpHeaders {
-    type Item = (CaseInsensitiveString, String);
-    type IntoIter = hash_map::IntoIter<CaseInsensitiveString, String>;
+    type Item = Header;
+    type IntoIter = vec::IntoIter<Header>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.0.into_iter()
@@ -93,19 +146,19 @@ impl IntoIterator for HttpHeaders {
 
 impl<'a> IntoIterator for &'a HttpHeaders {
     type Item = (&'a CaseInsensitiveString, &'a String);
-    type IntoIter = hash_map::Iter<'a, CaseInsensitiveString, String>;
+    type IntoIter = Iter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.iter()
+        self.iter()
     }
 }
 
 impl<'a> IntoIterator for &'a mut HttpHeaders {
     type Item = (&'a CaseInsensitiveString, &'a mut String);
-    type IntoIter = hash_map::IterMut<'a, CaseInsensitiveString, String>;
+    type IntoIter = IterMut<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.iter_mut()
+        self.0.iter_mut().map(|(k, v)| (&*k, v))
     }
 }
 
@@ -115,12 +168,21 @@ impl PartialEq for HttpHeaders {
     }
 }
 
+/// Builds an [`HttpHeaders`] from `name => value` pairs.
+///
+/// ```
+/// let headers = sip::headers! {
+///     "Accept" => "application/json",
+///     "X-Tenant" => "staging",
+/// };
+/// assert_eq!(headers.get("accept").unwrap(), "application/json");
+/// ```
 #[macro_export]
 macro_rules! headers {
     ( $($k:expr => $v:expr),* $(,)? ) => {{
-        let mut headers = hteapot::HttpHeaders::new();
+        let mut headers = $crate::http::HttpHeaders::new();
         $( headers.insert($k, $v); )*
-        Some(headers)
+        headers
     }};
 }
 
@@ -145,3 +207,34 @@ fn test_remove() {
     assert!(headers.remove("x-test-header").is_some());
     assert!(headers.get("x-test-header").is_none());
 }
+
+#[cfg(test)]
+#[test]
+fn test_multiple_values() {
+    let mut headers = HttpHeaders::new();
+    headers.append("Set-Cookie", "a=1");
+    headers.append("Via", "1.1 proxy");
+    headers.append("set-cookie", "b=2");
+    assert_eq!(headers.get("set-cookie").unwrap(), "a=1");
+    let cookies: Vec<&String> = headers.get_all("SET-COOKIE").collect();
+    assert_eq!(cookies, ["a=1", "b=2"]);
+    assert_eq!(headers.len(), 3);
+
+    headers.insert("Set-Cookie", "c=3");
+    let cookies: Vec<&String> = headers.get_all("set-cookie").collect();
+    assert_eq!(cookies, ["c=3"]);
+    assert_eq!(headers.len(), 2);
+}
+
+#[cfg(test)]
+#[test]
+fn test_order_and_case() {
+    let mut headers = HttpHeaders::new();
+    headers.insert("Host", "example.org");
+    headers.insert("X-Zeta", "1");
+    headers.insert("accept", "*/*");
+    headers.insert("x-zeta", "2");
+    let keys: Vec<&str> = headers.iter().map(|(k, _)| &**k).collect();
+    assert_eq!(keys, ["Host", "X-Zeta", "accept"]);
+    assert_eq!(headers.get("X-ZETA").unwrap(), "2");
+}
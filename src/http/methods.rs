@@ -4,15 +4,25 @@
 /// and a catch-all variant `Other(String)` for unknown or non-standard methods.
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum HttpMethod {
+    /// `GET`
     Get,
+    /// `POST`
     Post,
+    /// `PUT`
     Put,
+    /// `DELETE`
     Delete,
+    /// `PATCH`
     Patch,
+    /// `HEAD`
     Head,
+    /// `OPTIONS`
     Options,
+    /// `TRACE`
     Trace,
+    /// `CONNECT`
     Connect,
+    /// Any other method, kept as written.
     Other(String),
 }
 impl HttpMethod {
@@ -23,14 +33,15 @@ impl HttpMethod {
     ///
     /// # Examples
     /// ```
-    /// use hteapot::HttpMethod;
+    /// use sip::http::HttpMethod;
     ///
     /// let m = HttpMethod::from_str("GET");
-    /// assert_eq!(m, HttpMethod::GET);
+    /// assert_eq!(m, HttpMethod::Get);
     ///
     /// let custom = HttpMethod::from_str("CUSTOM");
     /// assert_eq!(custom, HttpMethod::Other("CUSTOM".into()));
     /// ```
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(method: &str) -> HttpMethod {
         let method = method.to_uppercase();
         match method.as_str() {
@@ -53,9 +64,9 @@ impl HttpMethod {
     ///
     /// # Examples
     /// ```
-    /// use hteapot::HttpMethod;
+    /// use sip::http::HttpMethod;
     ///
-    /// let method = HttpMethod::GET;
+    /// let method = HttpMethod::Get;
     /// assert_eq!(method.to_str(), "GET");
     /// ```
     pub fn to_str(&self) -> &str {
@@ -72,6 +83,20 @@ impl HttpMethod {
             HttpMethod::Other(method) => method.as_str(),
         }
     }
+
+    /// Returns true if sending the request twice has the same effect as
+    /// sending it once (RFC 9110 §9.2.2), so it's safe to replay.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            HttpMethod::Get
+                | HttpMethod::Head
+                | HttpMethod::Options
+                | HttpMethod::Trace
+                | HttpMethod::Put
+                | HttpMethod::Delete
+        )
+    }
 }
 
 // #[derive(Clone, Copy)]
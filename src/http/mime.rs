@@ -0,0 +1,162 @@
+//! Media types and the charsets bodies are decoded with.
+
+use std::fmt::{self, Display, Formatter};
+
+/// A parsed `Content-Type` value, like `text/html; charset=utf-8`.
+///
+/// The type, subtype and parameter names are lowercase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    /// The top-level type, like `text`.
+    pub kind: String,
+    /// The subtype, like `html`.
+    pub subtype: String,
+    /// Parameters in order, with lowercase names and unquoted values.
+    pub params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    /// Parses a media type, `None` if it has no `type/subtype`.
+    pub fn parse(value: &str) -> Option<MediaType> {
+        let mut parts = value.split(';');
+        let (kind, subtype) = parts.next()?.trim().split_once('/')?;
+        if kind.is_empty() || subtype.is_empty() {
+            return None;
+        }
+        let params = parts
+            .filter_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                Some((name.trim().to_lowercase(), value.to_string()))
+            })
+            .collect();
+        Some(MediaType {
+            kind: kind.to_lowercase(),
+            subtype: subtype.to_lowercase(),
+            params,
+        })
+    }
+
+    /// Returns `type/subtype` without the parameters.
+    pub fn essence(&self) -> String {
+        format!("{}/{}", self.kind, self.subtype)
+    }
+
+    /// Returns the value of the parameter `name`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the `charset` parameter.
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    /// Returns true for JSON, including `+json` types like
+    /// `application/problem+json`.
+    pub fn is_json(&self) -> bool {
+        self.subtype == "json" || self.subtype.ends_with("+json")
+    }
+}
+
+impl Display for MediaType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.kind, self.subtype)?;
+        for (k, v) in &self.params {
+            write!(f, "; {}={}", k, v)?;
+        }
+        Ok(())
+    }
+}
+
+/// What windows-1252 puts in 0x80..=0x9F, where latin-1 has control codes.
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Decodes `bytes` with `charset`, or UTF-8 if it's `None`.
+///
+/// A byte order mark wins over the charset, like browsers do. Invalid
+/// sequences become U+FFFD.
+pub fn decode(bytes: &[u8], charset: Option<&str>) -> Result<String, &'static str> {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return Ok(String::from_utf8_lossy(rest).into_owned());
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        return Ok(decode_utf16(rest, false));
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        return Ok(decode_utf16(rest, true));
+    }
+    let charset = charset.unwrap_or("utf-8").trim().to_lowercase();
+    match charset.as_str() {
+        "utf-8" | "utf8" | "us-ascii" | "ascii" => Ok(String::from_utf8_lossy(bytes).into_owned()),
+        "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "latin-1" | "l1" => {
+            Ok(bytes.iter().map(|&b| b as char).collect())
+        }
+        "windows-1252" | "cp1252" => Ok(bytes
+            .iter()
+            .map(|&b| match b {
+                0x80..=0x9F => WINDOWS_1252[(b - 0x80) as usize],
+                _ => b as char,
+            })
+            .collect()),
+        // Without a byte order mark UTF-16 is big endian (RFC 2781)
+        "utf-16" | "utf-16be" => Ok(decode_utf16(bytes, true)),
+        "utf-16le" => Ok(decode_utf16(bytes, false)),
+        _ => Err("Unsupported charset"),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> String {
+    let units = bytes.chunks(2).map(|pair| match pair {
+        [a, b] if big_endian => u16::from_be_bytes([*a, *b]),
+        [a, b] => u16::from_le_bytes([*a, *b]),
+        // A lone trailing byte can't be decoded
+        _ => 0xFFFD,
+    });
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let media = MediaType::parse("Text/HTML; Charset=\"ISO-8859-1\"; q=1").unwrap();
+        assert_eq!(media.essence(), "text/html");
+        assert_eq!(media.charset(), Some("ISO-8859-1"));
+        assert_eq!(media.to_string(), "text/html; charset=ISO-8859-1; q=1");
+        assert!(
+            MediaType::parse("application/problem+json")
+                .unwrap()
+                .is_json()
+        );
+        assert_eq!(MediaType::parse("text"), None);
+        assert_eq!(MediaType::parse("/json"), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"caf\xC3\xA9", None).unwrap(), "café");
+        assert_eq!(decode(b"caf\xE9", Some("ISO-8859-1")).unwrap(), "café");
+        assert_eq!(decode(b"\x80 5", Some("windows-1252")).unwrap(), "€ 5");
+        assert_eq!(decode(b"\0h\0i", Some("utf-16")).unwrap(), "hi");
+        assert_eq!(decode(b"h\0i\0", Some("utf-16le")).unwrap(), "hi");
+        assert_eq!(decode(b"\xFF\xFEh\0i\0", Some("utf-8")).unwrap(), "hi");
+        assert_eq!(decode(b"\xD8\x3D\xDE\x00", Some("UTF-16BE")).unwrap(), "😀");
+        assert_eq!(decode(b"caf\xE9", None).unwrap(), "caf\u{FFFD}");
+        assert!(decode(b"hi", Some("koi8-r")).is_err());
+    }
+}
@@ -1,12 +1,53 @@
+//! The HTTP client: requests, responses and everything used to send them.
+//!
+//! [`HttpRequest::brew`] sends a request and returns an [`HttpResponse`].
+//! Responses are parsed by the sans-IO [`HttpResponseBuilder`], which only
+//! sees bytes and can be fed from any transport.
+
+#[cfg(feature = "async")]
+pub mod async_client;
+mod auth;
 mod brew;
+mod chunked;
+mod client;
+mod cookies;
+mod encoding;
 mod headers;
 mod methods;
+mod mime;
+#[cfg(feature = "oauth2")]
+mod oauth;
+mod proxy;
 mod request;
+mod resolver;
 mod response;
+mod retry;
+mod session;
+mod signing;
 mod status;
+mod tls;
+mod transport;
 
-pub use headers::HttpHeaders;
+pub use auth::{Auth, BasicAuth, BearerAuth, DigestAuth};
+pub use chunked::ChunkedDecoder;
+pub use client::{Client, RequestBuilder};
+pub use cookies::{Cookie, CookieJar};
+pub use encoding::accept_encoding;
+pub use headers::{CaseInsensitiveString, HttpHeaders};
 pub use methods::HttpMethod;
+pub use mime::MediaType;
+#[cfg(feature = "oauth2")]
+pub use oauth::{Grant, OAuth2};
+pub use proxy::{Proxy, ProxyScheme, env_no_proxy, no_proxy_matches, parse_no_proxy};
 pub use request::HttpRequest;
-pub use response::HttpResponse;
+pub use resolver::{
+    HostPort, HostsResolver, Resolver, StaticResolver, SystemResolver, parse_connect_to,
+    parse_resolve,
+};
+pub use response::{ConnectionInfo, HttpResponse, HttpResponseBuilder};
+pub use retry::{RetryPolicy, retry_after};
+pub use session::Session;
+pub use signing::{AwsSigV4, HmacSigner, SignatureEncoding, Signer};
 pub use status::HttpStatus;
+pub use tls::{CertificateInfo, ClientCert, TlsConfig, TlsInfo, TlsVersion};
+pub use transport::IpVersion;
@@ -10,20 +10,53 @@
 use crate::http::HttpHeaders;
 
 use super::HttpMethod;
-use std::{collections::HashMap, str};
+use super::proxy::Proxy;
+use super::resolver::{Resolver, SystemResolver};
+use super::retry::RetryPolicy;
+use super::signing::Signer;
+use super::tls::TlsConfig;
+use super::transport::IpVersion;
+use std::path::PathBuf;
+use std::str;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Represents a parsed HTTP request.
 ///
-/// Contains method, path, optional query arguments, headers, body, and a stream (for low-level access).
-#[derive(Debug)]
+/// Contains method, path, optional query arguments, headers and body.
+#[derive(Debug, Clone)]
 pub struct HttpRequest {
+    /// Uses TLS when `host` has no scheme.
     pub ssl: bool,
+    /// Host with an optional scheme and port, like `https://example.com:8443`.
     pub host: String,
+    /// The request method.
     pub method: HttpMethod,
+    /// Path with an optional query string, starting with `/`.
     pub path: String,
-    pub args: HashMap<String, String>,
+    /// Extra query arguments, added in order after the ones in `path`.
+    /// Names may repeat.
+    pub args: Vec<(String, String)>,
+    /// Header fields, sent in order.
     pub headers: HttpHeaders,
+    /// The body, sent as is.
     pub body: Vec<u8>,
+    /// Signs the request in `brew`, right before it is sent.
+    pub signer: Option<Arc<dyn Signer>>,
+    /// Settings for `https` connections.
+    pub tls: TlsConfig,
+    /// Finds the addresses of the host, the system resolver by default.
+    pub resolver: Arc<dyn Resolver>,
+    /// Proxy the request goes through, if any.
+    pub proxy: Option<Proxy>,
+    /// Sends the request over this Unix domain socket instead of TCP.
+    pub unix_socket: Option<PathBuf>,
+    /// Only connects to addresses of this family.
+    pub ip_version: Option<IpVersion>,
+    /// Sends the request again after transient failures.
+    pub retry: Option<RetryPolicy>,
+    /// Limits connecting and each read, 5 and 20 seconds by default.
+    pub timeout: Option<Duration>,
 }
 
 impl HttpRequest {
@@ -34,19 +67,30 @@ impl HttpRequest {
             method,
             host: host.to_string(),
             path: path.to_string(),
-            args: HashMap::new(),
+            args: Vec::new(),
             headers: HttpHeaders::new(),
             body: Vec::new(),
+            signer: None,
+            tls: TlsConfig::new(),
+            resolver: Arc::new(SystemResolver),
+            proxy: None,
+            unix_socket: None,
+            ip_version: None,
+            retry: None,
+            timeout: None,
         }
     }
 
+    /// Parses a request written as text: a `METHOD url` line, headers and the
+    /// body after the first line without a colon.
     pub fn parse(raw: String) -> Result<Self, &'static str> {
         let mut ssl = false;
         let mut lines = raw.split('\n');
         let status_line = lines.next().ok_or("invalid status")?.to_string();
         let (raw_method, path) = status_line.split_once(" ").ok_or("Invalud status")?;
         let raw_method = raw_method.trim();
-        let path = path.trim();
+        // Drop the optional HTTP version after the target
+        let path = path.split_whitespace().next().unwrap_or_default();
         if path.starts_with("https://") {
             ssl = true;
         }
@@ -60,20 +104,20 @@ impl HttpRequest {
         path.insert(0, '/');
         let method = HttpMethod::from_str(raw_method);
         let mut headers = HttpHeaders::new();
-        while let Some(line) = lines.next() {
+        for line in lines.by_ref() {
             if !line.contains(':') || line.len() <= 1 {
                 break;
             }
             let (k, v) = line.split_once(":").unwrap();
-            let k = k.trim().to_lowercase();
+            let k = k.trim();
             let v = v.trim();
-            headers.insert(&k, v);
+            headers.append(k, v);
         }
         if !headers.contains_key("host") {
             headers.insert("host", &host);
         }
         let mut body = Vec::new();
-        while let Some(line) = lines.next() {
+        for line in lines {
             body.extend_from_slice(line.as_bytes());
         }
         // if !headers.contains_key("content-length") && body.is_empty() {
@@ -84,37 +128,49 @@ impl HttpRequest {
             ssl,
             method,
             path,
-            args: HashMap::new(),
+            args: Vec::new(),
             headers,
             body,
+            signer: None,
+            tls: TlsConfig::new(),
+            resolver: Arc::new(SystemResolver),
+            proxy: None,
+            unix_socket: None,
+            ip_version: None,
+            retry: None,
+            timeout: None,
         };
 
         Ok(request)
     }
 
-    /// Returns a blank default request (empty method/path/headers).
-    pub fn default() -> Self {
-        HttpRequest {
-            method: HttpMethod::Other(String::new()),
-            ssl: false,
-            path: String::new(),
-            host: String::new(),
-            args: HashMap::new(),
-            headers: HttpHeaders::new(),
-            body: Vec::new(),
-        }
+    /// Returns whether the connection uses TLS, the host name and the port.
+    ///
+    /// TLS follows the scheme (or `ssl`), never the port, so `http://host:443`
+    /// stays plain text. IPv6 literals are returned without brackets.
+    pub fn endpoint(&self) -> Result<(bool, String, u16), &'static str> {
+        let (ssl, authority) = self.scheme_and_authority();
+        let authority = authority.split('/').next().unwrap_or_default();
+        let (host, port) = split_authority(authority)?;
+        let port = port.unwrap_or(if ssl { 443 } else { 80 });
+        Ok((ssl, host.to_string(), port))
     }
 
-    /// Returns a blank default request (empty method/path/headers).
-    pub fn clone(&self) -> Self {
-        HttpRequest {
-            method: self.method.clone(),
-            ssl: self.ssl,
-            host: self.host.clone(),
-            path: self.path.clone(),
-            args: self.args.clone(),
-            headers: self.headers.clone(),
-            body: self.body.clone(),
+    /// Returns the full URL, like `https://host:port/path?query`.
+    pub fn url(&self) -> String {
+        let (ssl, authority) = self.scheme_and_authority();
+        let scheme = if ssl { "https" } else { "http" };
+        format!("{}://{}{}", scheme, authority, self.target())
+    }
+
+    /// Splits a scheme off `host`, falling back to `ssl` without one.
+    fn scheme_and_authority(&self) -> (bool, &str) {
+        if let Some(rest) = self.host.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = self.host.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            (self.ssl, self.host.as_str())
         }
     }
 
@@ -129,3 +185,91 @@ impl HttpRequest {
         }
     }
 }
+
+impl Default for HttpRequest {
+    /// Returns a blank default request (empty method/path/headers).
+    fn default() -> Self {
+        HttpRequest {
+            method: HttpMethod::Other(String::new()),
+            ssl: false,
+            path: String::new(),
+            host: String::new(),
+            args: Vec::new(),
+            headers: HttpHeaders::new(),
+            body: Vec::new(),
+            signer: None,
+            tls: TlsConfig::new(),
+            resolver: Arc::new(SystemResolver),
+            proxy: None,
+            unix_socket: None,
+            ip_version: None,
+            retry: None,
+            timeout: None,
+        }
+    }
+}
+
+/// Splits `host[:port]` or `[v6]:port` into the host and the port.
+pub(crate) fn split_authority(authority: &str) -> Result<(&str, Option<u16>), &'static str> {
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or("Invalid IPv6 address")?;
+        match rest {
+            "" => (host, None),
+            _ => (host, Some(rest.strip_prefix(':').ok_or("Invalid port")?)),
+        }
+    } else if authority.matches(':').count() > 1 {
+        // A bare IPv6 address can't carry a port
+        (authority, None)
+    } else {
+        match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty() {
+        return Err("Missing host");
+    }
+    let port = port
+        .map(|p| p.parse::<u16>().map_err(|_| "Invalid port"))
+        .transpose()?;
+    Ok((host, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint() {
+        let mut request = HttpRequest::new(HttpMethod::Get, "http://example.com:443", "/");
+        assert_eq!(
+            request.endpoint().unwrap(),
+            (false, "example.com".into(), 443)
+        );
+        request.host = "https://example.com:8443/x".to_string();
+        assert_eq!(
+            request.endpoint().unwrap(),
+            (true, "example.com".into(), 8443)
+        );
+        request.host = "[::1]:8080".to_string();
+        assert_eq!(request.endpoint().unwrap(), (false, "::1".into(), 8080));
+        request.host = "[2001:db8::1]".to_string();
+        request.ssl = true;
+        assert_eq!(
+            request.endpoint().unwrap(),
+            (true, "2001:db8::1".into(), 443)
+        );
+        request.host = "example.com:http".to_string();
+        assert!(request.endpoint().is_err());
+
+        let mut request = HttpRequest::new(HttpMethod::Get, "example.com:8080", "/a?b=1");
+        request.args.push(("c".into(), "2".into()));
+        assert_eq!(request.url(), "http://example.com:8080/a?b=1&c=2");
+
+        let request = HttpRequest::parse("GET https://example.com:8443/a\n".to_string()).unwrap();
+        assert_eq!(
+            request.endpoint().unwrap(),
+            (true, "example.com".into(), 8443)
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! Turning host names into addresses to connect to.
+//!
+//! [`SystemResolver`] asks the operating system. [`HostsResolver`] reads a
+//! hosts file and [`StaticResolver`] holds `--resolve` and `--connect-to`
+//! overrides; both fall back to another resolver for the names they don't
+//! know, so they can be stacked.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::fs;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Arc;
+
+/// A host name and a port.
+pub type HostPort = (String, u16);
+
+/// Finds the addresses to connect to for a host.
+pub trait Resolver: Debug + Send + Sync {
+    /// Returns the addresses for `host` and `port`, in preference order.
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, &'static str>;
+}
+
+/// Resolves names with `getaddrinfo` or the platform equivalent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, &'static str> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        }
+        Ok((host, port)
+            .to_socket_addrs()
+            .map_err(|_| "Unable to resolve domain")?
+            .collect())
+    }
+}
+
+/// Resolves names from a hosts file, like `/etc/hosts`.
+#[derive(Debug, Clone)]
+pub struct HostsResolver {
+    entries: HashMap<String, Vec<IpAddr>>,
+    fallback: Arc<dyn Resolver>,
+}
+
+impl HostsResolver {
+    /// Reads the entries of a hosts file, other names go to `fallback`.
+    pub fn parse(content: &str, fallback: Arc<dyn Resolver>) -> HostsResolver {
+        let mut entries: HashMap<String, Vec<IpAddr>> = HashMap::new();
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(Ok(ip)) = fields.next().map(|ip| ip.parse::<IpAddr>()) else {
+                continue;
+            };
+            for name in fields {
+                let addrs = entries.entry(name.to_lowercase()).or_default();
+                if !addrs.contains(&ip) {
+                    addrs.push(ip);
+                }
+            }
+        }
+        HostsResolver { entries, fallback }
+    }
+
+    /// Reads the hosts file at `path`, other names go to `fallback`.
+    pub fn load(path: &Path, fallback: Arc<dyn Resolver>) -> Result<HostsResolver, &'static str> {
+        let content = fs::read_to_string(path).map_err(|_| "Error reading hosts file")?;
+        Ok(HostsResolver::parse(&content, fallback))
+    }
+}
+
+impl Resolver for HostsResolver {
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, &'static str> {
+        match self.entries.get(&host.to_lowercase()) {
+            Some(ips) => Ok(ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect()),
+            None => self.fallback.resolve(host, port),
+        }
+    }
+}
+
+/// Fixed answers for some hosts, like curl's `--resolve` and `--connect-to`.
+#[derive(Debug, Clone)]
+pub struct StaticResolver {
+    addrs: BTreeMap<(String, u16), Vec<IpAddr>>,
+    /// `(host, port)` to connect to instead of another; an empty host or a
+    /// zero port in the key matches any.
+    connect_to: Vec<(HostPort, HostPort)>,
+    fallback: Arc<dyn Resolver>,
+}
+
+impl StaticResolver {
+    /// Creates a resolver without overrides, every name goes to `fallback`.
+    pub fn new(fallback: Arc<dyn Resolver>) -> Self {
+        StaticResolver {
+            addrs: BTreeMap::new(),
+            connect_to: Vec::new(),
+            fallback,
+        }
+    }
+
+    /// Uses `addrs` for `host:port`.
+    pub fn insert(&mut self, host: &str, port: u16, addrs: Vec<IpAddr>) {
+        self.addrs.insert((host.to_lowercase(), port), addrs);
+    }
+
+    /// Connects to `to` whenever `from` is requested.
+    pub fn connect_to(&mut self, from: (&str, u16), to: (&str, u16)) {
+        let from = (from.0.to_lowercase(), from.1);
+        self.connect_to.push((from, (to.0.to_string(), to.1)));
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, &'static str> {
+        let host = host.to_lowercase();
+        let mut target = (host.as_str(), port);
+        let rule = self.connect_to.iter().find(|((from_host, from_port), _)| {
+            (from_host.is_empty() || *from_host == host) && (*from_port == 0 || *from_port == port)
+        });
+        if let Some((_, (to_host, to_port))) = rule {
+            if !to_host.is_empty() {
+                target.0 = to_host;
+            }
+            if *to_port != 0 {
+                target.1 = *to_port;
+            }
+        }
+        match self.addrs.get(&(target.0.to_string(), target.1)) {
+            Some(ips) => Ok(ips
+                .iter()
+                .map(|ip| SocketAddr::new(*ip, target.1))
+                .collect()),
+            None => self.fallback.resolve(target.0, target.1),
+        }
+    }
+}
+
+/// Parses a curl style `host:port:addr[,addr]` override.
+///
+/// IPv6 addresses may be written with or without brackets.
+pub fn parse_resolve(spec: &str) -> Result<(String, u16, Vec<IpAddr>), &'static str> {
+    let mut parts = spec.splitn(3, ':');
+    let host = parts
+        .next()
+        .filter(|h| !h.is_empty())
+        .ok_or("Expected host:port:addr")?;
+    let port = parts
+        .next()
+        .ok_or("Expected host:port:addr")?
+        .parse::<u16>()
+        .map_err(|_| "Invalid port")?;
+    let addrs = parts.next().ok_or("Expected host:port:addr")?;
+    let addrs = addrs
+        .split(',')
+        .map(|a| {
+            a.trim()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .map_err(|_| "Invalid address")
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((host.to_lowercase(), port, addrs))
+}
+
+/// Parses a curl style `host1:port1:host2:port2` connect-to rule.
+///
+/// Any part may be empty, an empty `host1` or `port1` matches everything
+/// and an empty `host2` or `port2` keeps the requested one.
+pub fn parse_connect_to(spec: &str) -> Result<(HostPort, HostPort), &'static str> {
+    let mut parts = Vec::new();
+    let mut rest = spec;
+    for _ in 0..3 {
+        let part;
+        (part, rest) = split_field(rest).ok_or("Expected host1:port1:host2:port2")?;
+        parts.push(part);
+    }
+    parts.push(rest.trim_start_matches('[').trim_end_matches(']'));
+    let port = |p: &str| match p {
+        "" => Ok(0),
+        _ => p.parse::<u16>().map_err(|_| "Invalid port"),
+    };
+    Ok((
+        (parts[0].to_lowercase(), port(parts[1])?),
+        (parts[2].to_string(), port(parts[3])?),
+    ))
+}
+
+/// Splits off the first `:` separated field, which may be a bracketed IPv6
+/// address.
+fn split_field(spec: &str) -> Option<(&str, &str)> {
+    if let Some(rest) = spec.strip_prefix('[') {
+        let (field, rest) = rest.split_once(']')?;
+        return Some((field, rest.strip_prefix(':')?));
+    }
+    spec.split_once(':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every name with 192.0.2.1, to see when the fallback is used.
+    #[derive(Debug)]
+    struct Fallback;
+
+    impl Resolver for Fallback {
+        fn resolve(&self, _host: &str, port: u16) -> Result<Vec<SocketAddr>, &'static str> {
+            Ok(vec![SocketAddr::from(([192, 0, 2, 1], port))])
+        }
+    }
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_hosts_file() {
+        let hosts = "127.0.0.1 localhost # loopback\n\
+                     ::1\tlocalhost ip6-localhost\n\
+                     # 10.0.0.1 commented.example\n\
+                     10.0.0.2 API.example api\n\
+                     bogus line\n";
+        let resolver = HostsResolver::parse(hosts, Arc::new(Fallback));
+        assert_eq!(
+            resolver.resolve("localhost", 80).unwrap(),
+            vec![addr("127.0.0.1:80"), addr("[::1]:80")]
+        );
+        assert_eq!(
+            resolver.resolve("api.EXAMPLE", 443).unwrap(),
+            vec![addr("10.0.0.2:443")]
+        );
+        assert_eq!(
+            resolver.resolve("mylocalhost.dev", 80).unwrap(),
+            vec![addr("192.0.2.1:80")]
+        );
+        assert_eq!(
+            resolver.resolve("commented.example", 80).unwrap(),
+            vec![addr("192.0.2.1:80")]
+        );
+    }
+
+    #[test]
+    fn test_static() {
+        let mut resolver = StaticResolver::new(Arc::new(Fallback));
+        resolver.insert("Example.com", 443, vec!["::1".parse().unwrap()]);
+        resolver.connect_to(("api.example", 443), ("example.com", 0));
+        resolver.connect_to(("", 8080), ("backend", 9090));
+        assert_eq!(
+            resolver.resolve("example.com", 443).unwrap(),
+            vec![addr("[::1]:443")]
+        );
+        assert_eq!(
+            resolver.resolve("example.com", 80).unwrap(),
+            vec![addr("192.0.2.1:80")]
+        );
+        assert_eq!(
+            resolver.resolve("API.example", 443).unwrap(),
+            vec![addr("[::1]:443")]
+        );
+        assert_eq!(
+            resolver.resolve("anything", 8080).unwrap(),
+            vec![addr("192.0.2.1:9090")]
+        );
+    }
+
+    #[test]
+    fn test_pinned_request() {
+        use crate::http::{HttpMethod, HttpRequest};
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = stream.read(&mut buffer).unwrap();
+            let _ = stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n");
+        });
+
+        let hosts = "127.0.0.1 api.internal\n";
+        let mut resolver =
+            StaticResolver::new(Arc::new(HostsResolver::parse(hosts, Arc::new(Fallback))));
+        resolver.connect_to(("api.internal", 80), ("", port));
+        let mut request = HttpRequest::new(HttpMethod::Get, "http://api.internal", "/");
+        request.resolver = Arc::new(resolver);
+        let response = request.brew().unwrap();
+        assert_eq!(
+            response.connection.remote_addr,
+            Some(addr(&format!("127.0.0.1:{}", port)))
+        );
+    }
+
+    #[test]
+    fn test_system() {
+        assert_eq!(
+            SystemResolver.resolve("::1", 80).unwrap(),
+            vec![addr("[::1]:80")]
+        );
+        assert!(!SystemResolver.resolve("localhost", 80).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_resolve() {
+        let (host, port, addrs) = parse_resolve("Example.com:443:127.0.0.1,[::1]").unwrap();
+        assert_eq!((host.as_str(), port), ("example.com", 443));
+        assert_eq!(
+            addrs,
+            vec![IpAddr::from([127, 0, 0, 1]), "::1".parse().unwrap()]
+        );
+        assert!(parse_resolve("example.com:443").is_err());
+        assert!(parse_resolve("example.com:x:127.0.0.1").is_err());
+    }
+
+    #[test]
+    fn test_parse_connect_to() {
+        assert_eq!(
+            parse_connect_to("Example.com:443:backend:8443").unwrap(),
+            (("example.com".into(), 443), ("backend".into(), 8443))
+        );
+        assert_eq!(
+            parse_connect_to("::[::1]:").unwrap(),
+            (("".into(), 0), ("::1".into(), 0))
+        );
+        assert!(parse_connect_to("a:1:b").is_err());
+        assert!(parse_connect_to("a:x:b:1").is_err());
+    }
+}
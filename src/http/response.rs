@@ -1,75 +1,216 @@
-//! HTTP response types for HTeaPot.
+//! HTTP responses and the incremental parser that reads them.
+
+use std::net::SocketAddr;
 
 use crate::http::HttpHeaders;
 
-use super::HttpStatus;
+use super::chunked::ChunkedDecoder;
+use super::mime::{MediaType, decode};
+use super::tls::TlsInfo;
+use super::{HttpMethod, HttpStatus};
+
+/// How a response was received.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionInfo {
+    /// Address of the server the request was sent to.
+    pub remote_addr: Option<SocketAddr>,
+    /// Handshake details for `https` connections.
+    pub tls: Option<TlsInfo>,
+}
 
 /// Basic HTTP status line + headers.
+#[derive(Clone)]
 pub struct HttpResponse {
+    /// The status code.
     pub status: HttpStatus,
+    /// Reason phrase sent by the server, which may differ from `status.as_str()`.
+    pub reason: String,
+    /// Header fields, in the order they were received.
     pub headers: HttpHeaders,
+    /// The body, decoded from chunked transfer coding but still compressed
+    /// until [`decompress`](Self::decompress).
     pub content: Vec<u8>,
+    /// Trailer fields sent after a chunked body.
+    pub trailers: HttpHeaders,
+    /// Interim 1xx responses received before this one, in order.
+    pub informational: Vec<HttpResponse>,
+    /// Where the response came from.
+    pub connection: ConnectionInfo,
+}
+
+impl HttpResponse {
+    /// Returns the body as bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.content
+    }
+
+    /// Returns the parsed `Content-Type`, if there is a valid one.
+    pub fn content_type(&self) -> Option<MediaType> {
+        MediaType::parse(self.headers.get("content-type")?)
+    }
+
+    /// Returns the `Content-Length` header, which is removed by `decompress`.
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers.get("content-length")?.trim().parse().ok()
+    }
+
+    /// Decodes the body with the `charset` of its `Content-Type`, UTF-8 by
+    /// default.
+    pub fn text(&self) -> Result<String, &'static str> {
+        let content_type = self.content_type();
+        decode(
+            &self.content,
+            content_type.as_ref().and_then(|m| m.charset()),
+        )
+    }
+
+    /// Deserializes the body as JSON, whatever the `Content-Type` says.
+    #[cfg(feature = "serde")]
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, &'static str> {
+        serde_json::from_str(&self.text()?).map_err(|_| "Invalid JSON body")
+    }
+
+    /// Turns 4xx and 5xx responses into an error, the status reason phrase.
+    pub fn error_for_status(self) -> Result<Self, &'static str> {
+        self.error_for_status_ref()?;
+        Ok(self)
+    }
+
+    /// Like [`error_for_status`](Self::error_for_status), without consuming
+    /// the response.
+    pub fn error_for_status_ref(&self) -> Result<&Self, &'static str> {
+        if self.status.is_client_error() || self.status.is_server_error() {
+            return Err(self.status.as_str());
+        }
+        Ok(self)
+    }
 }
 
 use std::cmp::min;
 
 #[derive(Debug)]
-pub enum State {
+enum State {
     Init,
     Headers,
     Body,
     Finish,
 }
 
+/// Parses a response from bytes as they arrive, without doing any IO.
+///
+/// Feed it with [`append`](Self::append) until it returns `true`, then take
+/// the response with [`get`](Self::get).
 pub struct HttpResponseBuilder {
-    pub body: Vec<u8>,
-    pub headers: HttpHeaders,
-    pub status: HttpStatus,
+    body: Vec<u8>,
+    headers: HttpHeaders,
+    status: HttpStatus,
+    reason: String,
+    trailers: HttpHeaders,
+    informational: Vec<HttpResponse>,
+    connection: ConnectionInfo,
     buffer: Vec<u8>,
-    pub state: State,
-    chunked: bool,
-    length: usize,
+    state: State,
+    chunked: Option<ChunkedDecoder>,
+    length: Option<usize>,
+    /// Method of the request, `HEAD` replies end after the headers.
+    method: HttpMethod,
+}
+
+impl Default for HttpResponseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HttpResponseBuilder {
+    /// Creates a parser for the reply to a `GET`, waiting for the status line.
     pub fn new() -> HttpResponseBuilder {
+        HttpResponseBuilder::for_method(&HttpMethod::Get)
+    }
+
+    /// Creates a parser for the reply to a `method` request.
+    ///
+    /// Replies to `HEAD`, and successful ones to `CONNECT`, have no body
+    /// whatever their headers say.
+    pub fn for_method(method: &HttpMethod) -> HttpResponseBuilder {
         HttpResponseBuilder {
             status: HttpStatus::IAmATeapot,
+            reason: String::new(),
             headers: HttpHeaders::new(),
+            trailers: HttpHeaders::new(),
+            informational: Vec::new(),
+            connection: ConnectionInfo::default(),
             body: Vec::new(),
             buffer: Vec::new(),
             state: State::Init,
-            length: 0,
-            chunked: false,
+            length: None,
+            chunked: None,
+            method: method.clone(),
         }
     }
 
+    /// Returns the status parsed so far, `418` before the status line.
+    pub fn status(&self) -> HttpStatus {
+        self.status
+    }
+
+    /// Returns the headers parsed so far.
+    pub fn headers(&self) -> &HttpHeaders {
+        &self.headers
+    }
+
+    /// Returns the interim 1xx responses received so far.
+    pub fn informational(&self) -> &[HttpResponse] {
+        &self.informational
+    }
+
+    /// Returns true once the whole response has been read.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, State::Finish)
+    }
+
+    /// Gives access to the connection details copied into the response.
+    pub fn connection_mut(&mut self) -> &mut ConnectionInfo {
+        &mut self.connection
+    }
+
+    /// Returns the response once it's complete.
     pub fn get(&self) -> Option<HttpResponse> {
         match self.state {
             State::Finish => Some(HttpResponse {
                 status: self.status,
+                reason: self.reason.clone(),
                 headers: self.headers.clone(),
                 content: self.body.clone(),
+                trailers: self.trailers.clone(),
+                informational: self.informational.clone(),
+                connection: self.connection.clone(),
             }),
             _ => None,
         }
     }
 
+    /// Parses `chunk`, returning true when the response is complete.
     pub fn append(&mut self, chunk: &[u8]) -> Result<bool, &'static str> {
         self.buffer.extend_from_slice(chunk);
 
-        while !self.buffer.is_empty() {
+        loop {
             match self.state {
                 State::Init => {
                     if let Some(line) = get_line(&mut self.buffer) {
-                        let parts: Vec<&str> = line.split_whitespace().collect();
-                        if parts.len() < 3 {
-                            println!("parts: {:?}", parts);
+                        //HTTP/1.1 200 OK, the reason phrase may be empty
+                        let mut parts = line.splitn(3, ' ');
+                        let version = parts.next().unwrap_or_default();
+                        if !version.starts_with("HTTP/") {
                             return Err("Invalid response");
                         }
-                        let status_str = parts.get(1).ok_or("Invalid status")?;
+                        let status_str = parts.next().ok_or("Invalid status")?;
+                        if status_str.len() != 3 {
+                            return Err("Invalid status");
+                        }
                         let status = status_str.parse::<u16>().map_err(|_| "Invalid status")?;
-                        self.status = HttpStatus::from_u16(status).map_err(|_| "Invalid status")?;
+                        self.status = HttpStatus::from_u16(status)?;
+                        self.reason = parts.next().unwrap_or_default().trim().to_string();
                         self.state = State::Headers;
                     } else {
                         return Ok(false);
@@ -77,73 +218,88 @@ impl HttpResponseBuilder {
                 }
                 State::Headers => {
                     if let Some(line) = get_line(&mut self.buffer) {
+                        if line.is_empty() && self.status.is_interim() {
+                            // Keep interim responses and wait for the final one
+                            self.informational.push(HttpResponse {
+                                status: self.status,
+                                reason: std::mem::take(&mut self.reason),
+                                headers: std::mem::take(&mut self.headers),
+                                content: Vec::new(),
+                                trailers: HttpHeaders::new(),
+                                informational: Vec::new(),
+                                connection: ConnectionInfo::default(),
+                            });
+                            self.length = None;
+                            self.chunked = None;
+                            self.state = State::Init;
+                            continue;
+                        }
                         if line.is_empty() {
-                            self.state = State::Body;
+                            self.state = match self.has_body() {
+                                true => State::Body,
+                                false => State::Finish,
+                            };
                             continue;
                         }
                         let (k, v) = line.split_once(":").ok_or("Invalid header")?;
-                        let k = k.trim().to_lowercase();
+                        let k = k.trim();
                         let v = v.trim();
-                        if k == "content-length" {
-                            self.length = v.parse().unwrap_or(0);
+                        if k.eq_ignore_ascii_case("content-length") {
+                            self.length = Some(v.parse().map_err(|_| "Invalid content-length")?);
                         }
-                        if k == "transfer-encoding" && v.to_lowercase() == "chunked" {
-                            self.chunked = true;
-                            self.length = v.parse().unwrap_or(0);
+                        if k.eq_ignore_ascii_case("transfer-encoding") && is_chunked(v) {
+                            self.chunked = Some(ChunkedDecoder::new());
                         }
-                        self.headers.insert(&k, v);
+                        self.headers.append(k, v);
                     } else {
                         return Ok(false);
                     }
                 }
                 State::Body => {
-                    let body_left = self.length - self.body.len();
-                    if body_left > 0 {
-                        let to_take = min(body_left, self.buffer.len());
-                        let to_append = self.buffer.drain(..to_take);
-                        let to_append = to_append.as_slice();
-                        self.body.extend_from_slice(to_append);
-                        if to_append.len() < body_left {
-                            return Ok(false);
-                        }
-                    }
-
-                    if self.chunked {
-                        let size = get_line(&mut self.buffer);
-                        if size.is_none() {
-                            self.length = 0;
-                            return Ok(false);
-                        }
-                        let size = size.unwrap();
-                        if size.is_empty() {
-                            continue;
-                        }
-                        let size = size.strip_prefix("0x").unwrap_or(&size);
-                        let size =
-                            i64::from_str_radix(size, 16).map_err(|_| "Invalud chunk size")?;
-                        if size == 0 {
+                    // Transfer-Encoding takes precedence over Content-Length
+                    if let Some(decoder) = self.chunked.as_mut() {
+                        if decoder.decode(&mut self.buffer, &mut self.body, &mut self.trailers)? {
                             self.state = State::Finish;
-                            return Ok(true);
+                            continue;
                         }
-
-                        self.length += size as usize;
+                        return Ok(false);
                     }
 
-                    if self.body.len() >= self.length {
-                        self.state = State::Finish;
-                        return Ok(true);
+                    let length = self.length.unwrap_or(0);
+                    let to_take = min(length - self.body.len(), self.buffer.len());
+                    self.body.extend(self.buffer.drain(..to_take));
+                    if self.body.len() < length {
+                        return Ok(false);
                     }
+                    self.state = State::Finish;
                 }
                 State::Finish => {
                     return Ok(true);
                 }
             }
         }
+    }
 
-        Ok(false)
+    /// Returns false for responses without a body (RFC 9112 §6.3): replies to
+    /// `HEAD`, 1xx, 204 and 304 ones, and 2xx replies to `CONNECT`.
+    fn has_body(&self) -> bool {
+        let code = self.status.as_num();
+        !(self.method == HttpMethod::Head
+            || self.status.is_informational()
+            || code == 204
+            || code == 304
+            || (self.method == HttpMethod::Connect && self.status.is_success()))
     }
 }
 
+/// Returns true if `chunked` is the final transfer coding in the header value.
+fn is_chunked(transfer_encoding: &str) -> bool {
+    transfer_encoding
+        .rsplit(',')
+        .next()
+        .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+}
+
 fn get_line(buffer: &mut Vec<u8>) -> Option<String> {
     if let Some(pos) = buffer.windows(2).position(|w| w == b"\r\n") {
         let line = buffer.drain(..pos).collect::<Vec<u8>>();
@@ -155,3 +311,183 @@ fn get_line(buffer: &mut Vec<u8>) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNKED: &[u8] = b"HTTP/1.1 200 OK\r\n\
+Content-Type: text/plain\r\n\
+Transfer-Encoding: chunked\r\n\
+Trailer: Expires\r\n\
+\r\n\
+7;foo=bar\r\nMozilla\r\n\
+11\r\nDeveloper Network\r\n\
+0\r\n\
+Expires: Wed, 21 Oct 2015 07:28:00 GMT\r\n\
+\r\n";
+
+    const FIXED: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\nhello\r\nworld";
+
+    fn parse_split(input: &[u8], splits: &[usize]) -> HttpResponse {
+        let mut builder = HttpResponseBuilder::new();
+        let mut last = 0;
+        let mut done = false;
+        for &split in splits.iter().chain(std::iter::once(&input.len())) {
+            done = builder.append(&input[last..split]).unwrap();
+            last = split;
+        }
+        assert!(done);
+        builder.get().unwrap()
+    }
+
+    #[test]
+    fn test_chunked_every_split() {
+        for i in 0..=CHUNKED.len() {
+            for j in i..=CHUNKED.len() {
+                let response = parse_split(CHUNKED, &[i, j]);
+                assert_eq!(response.content, b"MozillaDeveloper Network");
+                assert_eq!(
+                    response.trailers.get("expires").unwrap(),
+                    "Wed, 21 Oct 2015 07:28:00 GMT"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunked_byte_by_byte() {
+        let splits: Vec<usize> = (1..CHUNKED.len()).collect();
+        let response = parse_split(CHUNKED, &splits);
+        assert_eq!(response.content, b"MozillaDeveloper Network");
+    }
+
+    #[test]
+    fn test_content_length_every_split() {
+        for i in 0..=FIXED.len() {
+            let response = parse_split(FIXED, &[i]);
+            assert_eq!(response.content, b"hello\r\nworld");
+            assert!(response.trailers.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_chunked_ignores_content_length() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\nTransfer-Encoding: gzip, chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n";
+        let response = parse_split(raw, &[]);
+        assert_eq!(response.content, b"ok");
+    }
+
+    #[test]
+    fn test_informational_every_split() {
+        let raw = b"HTTP/1.1 100 Continue\r\n\r\n\
+HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n\
+HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        for i in 0..=raw.len() {
+            let response = parse_split(raw, &[i]);
+            assert_eq!(response.status, HttpStatus::OK);
+            assert_eq!(response.content, b"ok");
+            assert!(!response.headers.contains_key("link"));
+            assert_eq!(response.informational.len(), 2);
+            assert_eq!(response.informational[0].status, HttpStatus::Continue);
+            assert_eq!(response.informational[1].status, HttpStatus::EarlyHints);
+            assert_eq!(
+                response.informational[1].headers.get("link").unwrap(),
+                "</style.css>; rel=preload"
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_status() {
+        let raw = b"HTTP/1.1 520 Web Server Returned an Unknown Error\r\nContent-Length: 0\r\n\r\n";
+        let response = parse_split(raw, &[]);
+        assert_eq!(response.status, HttpStatus::Other(520));
+        assert!(response.status.is_server_error());
+        assert_eq!(response.reason, "Web Server Returned an Unknown Error");
+
+        let response = parse_split(b"HTTP/1.1 299 \r\nContent-Length: 0\r\n\r\n", &[]);
+        assert_eq!(response.status, HttpStatus::Other(299));
+        assert!(response.reason.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_status_line() {
+        let mut builder = HttpResponseBuilder::new();
+        assert!(builder.append(b"HTTP/1.1 20 OK\r\n").is_err());
+        let mut builder = HttpResponseBuilder::new();
+        assert!(builder.append(b"garbage\r\n").is_err());
+    }
+
+    #[test]
+    fn test_repeated_headers() {
+        let raw = b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nVia: 1.1 one\r\nSet-Cookie: b=2\r\nContent-Length: 0\r\n\r\n";
+        let response = parse_split(raw, &[]);
+        let cookies: Vec<&String> = response.headers.get_all("set-cookie").collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        let keys: Vec<&str> = response.headers.iter().map(|(k, _)| &**k).collect();
+        assert_eq!(keys, ["Set-Cookie", "Via", "Set-Cookie", "Content-Length"]);
+    }
+
+    #[test]
+    fn test_helpers() {
+        let raw = b"HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=latin1\r\nContent-Length: 4\r\n\r\ncaf\xE9";
+        let response = parse_split(raw, &[]);
+        assert_eq!(response.content_type().unwrap().essence(), "text/plain");
+        assert_eq!(response.content_length(), Some(4));
+        assert_eq!(response.text().unwrap(), "café");
+        assert_eq!(response.bytes(), b"caf\xE9");
+        assert_eq!(response.error_for_status_ref().err(), Some("Not Found"));
+
+        let response = parse_split(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok", &[]);
+        assert_eq!(response.content_type(), None);
+        assert_eq!(response.error_for_status().unwrap().text().unwrap(), "ok");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: application/json; charset=utf-16le\r\nContent-Length: 14\r\n\r\n{\0\"\0a\0\"\0:\x001\0}\0";
+        let value: serde_json::Value = parse_split(raw, &[]).json().unwrap();
+        assert_eq!(value["a"], 1);
+    }
+
+    #[test]
+    fn test_empty_body() {
+        let response = parse_split(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n", &[]);
+        assert!(response.content.is_empty());
+    }
+
+    #[test]
+    fn test_responses_without_body() {
+        for raw in [
+            &b"HTTP/1.1 304 Not Modified\r\nContent-Length: 1234\r\n\r\n"[..],
+            b"HTTP/1.1 204 No Content\r\nContent-Length: 10\r\n\r\n",
+            b"HTTP/1.1 101 Switching Protocols\r\nContent-Length: 10\r\n\r\n",
+        ] {
+            let response = parse_split(raw, &[]);
+            assert!(response.content.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_head_reply() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 1234\r\n\r\n";
+        let mut builder = HttpResponseBuilder::for_method(&HttpMethod::Head);
+        assert!(builder.append(raw).unwrap());
+        let response = builder.get().unwrap();
+        assert_eq!(response.content_length(), Some(1234));
+        assert!(response.content.is_empty());
+
+        // The same reply to a GET waits for the body
+        let mut builder = HttpResponseBuilder::new();
+        assert!(!builder.append(raw).unwrap());
+
+        let mut builder = HttpResponseBuilder::for_method(&HttpMethod::Connect);
+        assert!(
+            builder
+                .append(b"HTTP/1.1 200 Connection established\r\nContent-Length: 5\r\n\r\n")
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Retrying requests that failed for reasons that may go away.
+//!
+//! A [`RetryPolicy`] on a request makes `brew` try again after connection
+//! errors, timeouts and statuses like `503`, waiting longer each time or as
+//! long as the server asks with `Retry-After`.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::brew::is_transient;
+use super::cookies::{parse_cookie_date, unix_now};
+use super::{HttpMethod, HttpResponse, HttpStatus};
+
+/// When and how often to send a request again.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Attempts after the first one.
+    pub retries: u32,
+    /// Wait before the first retry, doubled after each one.
+    pub delay: Duration,
+    /// Longest wait between two attempts, `Retry-After` included.
+    pub max_delay: Duration,
+    /// Stops retrying once this much time has passed since the first attempt.
+    pub max_time: Option<Duration>,
+    /// Statuses worth another attempt, [`HttpStatus::is_retryable`] ones if `None`.
+    pub statuses: Option<Vec<HttpStatus>>,
+    /// Replays methods that aren't idempotent, like `POST`, too.
+    pub all_methods: bool,
+}
+
+impl RetryPolicy {
+    /// Retries up to `retries` times, waiting 1s, 2s, 4s... up to a minute.
+    pub fn new(retries: u32) -> Self {
+        RetryPolicy {
+            retries,
+            delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            max_time: None,
+            statuses: None,
+            all_methods: false,
+        }
+    }
+
+    /// Returns true if requests with `method` may be sent again.
+    pub fn allows(&self, method: &HttpMethod) -> bool {
+        self.all_methods || method.is_idempotent()
+    }
+
+    /// Returns true if `result` is worth another attempt.
+    pub fn should_retry(&self, result: &Result<HttpResponse, &'static str>) -> bool {
+        match result {
+            Ok(response) => match &self.statuses {
+                Some(statuses) => statuses.contains(&response.status),
+                None => response.status.is_retryable(),
+            },
+            Err(e) => is_transient(e),
+        }
+    }
+
+    /// Returns how long to wait before retry number `retry`, counting from 0.
+    ///
+    /// A `Retry-After` header in `response` wins over the backoff, both are
+    /// capped at `max_delay`.
+    pub fn wait(&self, retry: u32, response: Option<&HttpResponse>) -> Duration {
+        let wait = match response.and_then(retry_after) {
+            Some(wait) => wait,
+            None => self.delay.saturating_mul(2u32.saturating_pow(retry)),
+        };
+        wait.min(self.max_delay)
+    }
+
+    /// Returns how long to wait before retry number `retry` after `result`,
+    /// `elapsed` after the first attempt, or `None` to stop.
+    pub fn next_wait(
+        &self,
+        retry: u32,
+        elapsed: Duration,
+        result: &Result<HttpResponse, &'static str>,
+    ) -> Option<Duration> {
+        if retry >= self.retries || !self.should_retry(result) {
+            return None;
+        }
+        let wait = self.wait(retry, result.as_ref().ok());
+        match self.max_time {
+            Some(max) if elapsed + wait > max => None,
+            _ => Some(wait),
+        }
+    }
+
+    /// Calls `send` until it succeeds, fails for good or the policy runs out.
+    ///
+    /// Returns the last result.
+    pub fn run<F>(&self, mut send: F) -> Result<HttpResponse, &'static str>
+    where
+        F: FnMut() -> Result<HttpResponse, &'static str>,
+    {
+        let start = Instant::now();
+        let mut retry = 0;
+        loop {
+            let result = send();
+            match self.next_wait(retry, start.elapsed(), &result) {
+                Some(wait) => thread::sleep(wait),
+                None => return result,
+            }
+            retry += 1;
+        }
+    }
+}
+
+/// Reads `Retry-After` as seconds or as an HTTP date.
+pub fn retry_after(response: &HttpResponse) -> Option<Duration> {
+    let value = response.headers.get("retry-after")?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = parse_cookie_date(value)?;
+    Some(Duration::from_secs(date.saturating_sub(unix_now())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpRequest;
+    use crate::http::response::HttpResponseBuilder;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn response(raw: &[u8]) -> HttpResponse {
+        let mut builder = HttpResponseBuilder::new();
+        assert!(builder.append(raw).unwrap());
+        builder.get().unwrap()
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut policy = RetryPolicy::new(5);
+        policy.delay = Duration::from_millis(100);
+        policy.max_delay = Duration::from_millis(500);
+        let waits: Vec<u128> = (0..5).map(|i| policy.wait(i, None).as_millis()).collect();
+        assert_eq!(waits, [100, 200, 400, 500, 500]);
+        assert_eq!(policy.wait(40, None), Duration::from_millis(500));
+
+        let busy = response(b"HTTP/1.1 503 Service Unavailable\r\nRetry-After: 120\r\n\r\n");
+        assert_eq!(policy.wait(0, Some(&busy)), Duration::from_millis(500));
+        policy.max_delay = Duration::from_secs(3600);
+        assert_eq!(policy.wait(0, Some(&busy)), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let past = response(
+            b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: Wed, 21 Oct 2015 07:28:00 GMT\r\n\r\n",
+        );
+        assert_eq!(retry_after(&past), Some(Duration::ZERO));
+        let bogus = response(b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: soon\r\n\r\n");
+        assert_eq!(retry_after(&bogus), None);
+        let garbled =
+            response("HTTP/1.1 503 Service Unavailable\r\nRetry-After: éé\r\n\r\n".as_bytes());
+        assert_eq!(retry_after(&garbled), None);
+        // Falls back to the backoff
+        assert_eq!(
+            RetryPolicy::new(1).wait(0, Some(&garbled)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(retry_after(&response(b"HTTP/1.1 200 OK\r\n\r\n")), None);
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::new(1);
+        assert!(policy.should_retry(&Err("Error connecting to server")));
+        assert!(policy.should_retry(&Err(crate::http::brew::CLOSED_ERROR)));
+        assert!(!policy.should_retry(&Err("Invalid port")));
+        assert!(policy.should_retry(&Ok(response(b"HTTP/1.1 502 Bad Gateway\r\n\r\n"))));
+        assert!(!policy.should_retry(&Ok(response(b"HTTP/1.1 500 Error\r\n\r\n"))));
+        assert!(policy.allows(&HttpMethod::Put));
+        assert!(!policy.allows(&HttpMethod::Post));
+    }
+
+    #[test]
+    fn test_max_time() {
+        let mut policy = RetryPolicy::new(10);
+        policy.delay = Duration::from_secs(5);
+        policy.max_time = Some(Duration::from_secs(1));
+        let mut attempts = 0;
+        let result = policy.run(|| {
+            attempts += 1;
+            Err("Error connecting to server")
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    /// Answers `503` with `Retry-After: 0` to the first `failures` requests.
+    fn flaky_server(failures: usize) -> (u16, std::thread::JoinHandle<usize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let mut served = 0;
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut buffer = [0u8; 1024];
+                let _ = stream.read(&mut buffer).unwrap();
+                served += 1;
+                if served <= failures {
+                    stream
+                        .write_all(b"HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\n\r\n")
+                        .unwrap();
+                } else {
+                    stream
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                        .unwrap();
+                    break;
+                }
+            }
+            served
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn test_retries_request() {
+        let (port, server) = flaky_server(2);
+        let mut request = HttpRequest::new(HttpMethod::Get, &format!("127.0.0.1:{}", port), "/");
+        request.retry = Some(RetryPolicy::new(3));
+        let response = request.brew().unwrap();
+        assert_eq!(response.status, HttpStatus::OK);
+        assert_eq!(response.content, b"ok");
+        assert_eq!(server.join().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_post_not_replayed() {
+        let (port, _server) = flaky_server(1);
+        let mut request = HttpRequest::new(HttpMethod::Post, &format!("127.0.0.1:{}", port), "/");
+        request.retry = Some(RetryPolicy::new(3));
+        let response = request.brew().unwrap();
+        assert_eq!(response.status, HttpStatus::ServiceUnavailable);
+
+        request.retry.as_mut().unwrap().all_methods = true;
+        let response = request.brew().unwrap();
+        assert_eq!(response.status, HttpStatus::OK);
+    }
+}
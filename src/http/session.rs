@@ -0,0 +1,258 @@
+//! Named sessions that keep headers and cookies between runs.
+//!
+//! A session file is a header block followed by an empty line and a Netscape
+//! cookie file, so it can be read and edited by hand:
+//!
+//! ```text
+//! Authorization: Bearer 1234
+//! X-Tenant: staging
+//!
+//! # Netscape HTTP Cookie File
+//! example.com   FALSE   /   FALSE   0   sid   abc
+//! ```
+//!
+//! Cookie fields are tab separated, as in any `cookies.txt`.
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::{CookieJar, HttpHeaders, HttpRequest, HttpResponse};
+
+/// Headers and cookies kept between runs.
+#[derive(Debug, Default, Clone)]
+pub struct Session {
+    /// Headers added to requests that don't set them.
+    pub headers: HttpHeaders,
+    /// Cookies sent with and stored from requests.
+    pub cookies: CookieJar,
+}
+
+impl Session {
+    /// Creates an empty session.
+    pub fn new() -> Self {
+        Session {
+            headers: HttpHeaders::new(),
+            cookies: CookieJar::new(),
+        }
+    }
+
+    /// Returns the file for a session.
+    ///
+    /// Plain names are stored per host under the user's config directory,
+    /// anything that looks like a path is used as is.
+    pub fn path_for(name: &str, host: &str) -> Option<PathBuf> {
+        Session::path_in(config_dir().as_deref(), name, host)
+    }
+
+    /// Returns the file for a session under the given config directory.
+    ///
+    /// Plain names give `None` when there is no config directory.
+    pub fn path_in(config_dir: Option<&Path>, name: &str, host: &str) -> Option<PathBuf> {
+        if name.contains('/') || name.contains('\\') || name.ends_with(".session") {
+            return Some(PathBuf::from(name));
+        }
+        let host = host
+            .strip_prefix("https://")
+            .or_else(|| host.strip_prefix("http://"))
+            .unwrap_or(host);
+        let host = host.split('/').next().unwrap_or(host).replace(':', "_");
+        let mut path = config_dir?.to_path_buf();
+        path.push("sip");
+        path.push("sessions");
+        path.push(host);
+        path.push(format!("{}.session", name));
+        Some(path)
+    }
+
+    /// Loads a session, a missing file gives an empty one.
+    pub fn load(path: &Path) -> Result<Session, &'static str> {
+        if !path.exists() {
+            return Ok(Session::new());
+        }
+        let content = fs::read_to_string(path).map_err(|_| "Error reading session")?;
+        Ok(Session::parse(&content))
+    }
+
+    /// Saves the session where only the current user can read it, since it
+    /// usually holds credentials.
+    pub fn save(&self, path: &Path) -> Result<(), &'static str> {
+        write_private(path, &self.to_string()).map_err(|_| "Error writing session")
+    }
+
+    /// Reads a session file, skipping lines it doesn't understand.
+    pub fn parse(content: &str) -> Session {
+        let mut session = Session::new();
+        let mut lines = content.lines();
+        for line in lines.by_ref() {
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((k, v)) = line.split_once(':') {
+                session.headers.append(k.trim(), v.trim());
+            }
+        }
+        let cookies: Vec<&str> = lines.collect();
+        session.cookies = CookieJar::from_netscape(&cookies.join("\n"));
+        session
+    }
+
+    /// Adds the session headers missing from `request` and its cookies.
+    pub fn apply(&self, request: &mut HttpRequest) {
+        for (k, v) in &self.headers {
+            if !request.headers.contains_key(k) {
+                request.headers.append(k, v);
+            }
+        }
+        self.cookies.apply(request);
+    }
+
+    /// Remembers the headers of `request` that make sense to replay.
+    ///
+    /// Like HTTPie, `Content-*` and `If-*` headers are skipped, as well as the
+    /// ones derived from the request itself.
+    pub fn update_headers(&mut self, headers: &HttpHeaders) {
+        for (k, v) in headers {
+            let name = k.to_lowercase();
+            if name.starts_with("content-")
+                || name.starts_with("if-")
+                || ["host", "cookie", "expect"].contains(&name.as_str())
+            {
+                continue;
+            }
+            self.headers.insert(k, v);
+        }
+    }
+
+    /// Stores the cookies set by `response`.
+    pub fn update(&mut self, request: &HttpRequest, response: &HttpResponse) {
+        self.cookies.store(request, response);
+    }
+}
+
+impl std::fmt::Display for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (k, v) in &self.headers {
+            writeln!(f, "{}: {}", k, v)?;
+        }
+        writeln!(f)?;
+        write!(f, "{}", self.cookies.to_netscape())
+    }
+}
+
+/// Returns the per-user configuration directory.
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        return env::var_os("APPDATA").map(PathBuf::from);
+    }
+    env::var_os("HOME").map(|home| Path::new(&home).join(".config"))
+}
+
+/// Writes a file only the current user can read, creating its directory.
+///
+/// On unix the directory is created with mode 0700 and the file is left
+/// with mode 0600, even when it already existed.
+pub(crate) fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(parent)?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpMethod;
+
+    #[test]
+    fn test_round_trip() {
+        let mut session = Session::new();
+        session.headers.insert("Authorization", "Bearer 1234");
+        session.headers.insert("X-Tenant", "staging");
+        let mut request = HttpRequest::new(HttpMethod::Get, "example.com", "/");
+        let mut response = crate::http::response::HttpResponseBuilder::new();
+        response
+            .append(b"HTTP/1.1 200 OK\r\nSet-Cookie: sid=abc\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        session.update(&request, &response.get().unwrap());
+
+        let loaded = Session::parse(&session.to_string());
+        assert_eq!(loaded.headers, session.headers);
+        assert_eq!(loaded.cookies.len(), 1);
+        let cookies_only = Session::parse(&Session::new().to_string());
+        assert!(cookies_only.headers.is_empty());
+
+        loaded.apply(&mut request);
+        assert_eq!(request.headers.get("authorization").unwrap(), "Bearer 1234");
+        assert_eq!(request.headers.get("cookie").unwrap(), "sid=abc");
+    }
+
+    #[test]
+    fn test_request_headers_win() {
+        let mut session = Session::new();
+        session.headers.insert("X-Tenant", "staging");
+        let mut request = HttpRequest::new(HttpMethod::Get, "example.com", "/");
+        request.headers.insert("X-Tenant", "prod");
+        request.headers.insert("Content-Type", "application/json");
+        session.apply(&mut request);
+        assert_eq!(request.headers.get("x-tenant").unwrap(), "prod");
+
+        session.update_headers(&request.headers);
+        assert_eq!(session.headers.get("x-tenant").unwrap(), "prod");
+        assert!(!session.headers.contains_key("content-type"));
+    }
+
+    #[test]
+    fn test_path_for() {
+        assert_eq!(
+            Session::path_for("./my.session", "example.com").unwrap(),
+            PathBuf::from("./my.session")
+        );
+        let dir = Path::new("/tmp/sip-config");
+        assert_eq!(
+            Session::path_in(Some(dir), "staging", "https://example.com:8443/me").unwrap(),
+            dir.join("sip/sessions/example.com_8443/staging.session")
+        );
+        assert_eq!(
+            Session::path_in(None, "./my.session", "example.com").unwrap(),
+            PathBuf::from("./my.session")
+        );
+        assert_eq!(Session::path_in(None, "staging", "example.com"), None);
+    }
+
+    #[test]
+    fn test_save() {
+        let dir = env::temp_dir().join(format!("sip-session-{}", std::process::id()));
+        let path = dir.join("private").join("test.session");
+        let mut session = Session::new();
+        session.headers.insert("Authorization", "Bearer 1234");
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap().headers, session.headers);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&path), 0o600);
+            assert_eq!(mode(path.parent().unwrap()), 0o700);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,420 @@
+//! Request signing.
+//!
+//! A [`Signer`] runs inside `brew` once the headers and body are final and
+//! right before the request is serialized, so the signature covers exactly
+//! what goes on the wire.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use super::HttpRequest;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signs requests once they are ready to be sent.
+pub trait Signer: fmt::Debug + Send + Sync {
+    /// Adds the signature headers to `request`.
+    fn sign(&self, request: &mut HttpRequest) -> Result<(), &'static str>;
+}
+
+/// AWS Signature Version 4.
+#[derive(Clone)]
+pub struct AwsSigV4 {
+    /// AWS access key id.
+    pub access_key: String,
+    /// AWS secret access key.
+    pub secret_key: String,
+    /// Temporary session token, sent as `X-Amz-Security-Token`.
+    pub session_token: Option<String>,
+    /// Region, like `eu-west-1`.
+    pub region: String,
+    /// Service, like `s3` or `execute-api`.
+    pub service: String,
+}
+
+impl fmt::Debug for AwsSigV4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsSigV4")
+            .field("access_key", &self.access_key)
+            .field("region", &self.region)
+            .field("service", &self.service)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Headers that intermediaries may change, so they are never signed.
+const UNSIGNED_HEADERS: &[&str] = &["authorization", "user-agent", "expect", "x-amzn-trace-id"];
+
+impl AwsSigV4 {
+    /// Creates a signer for `service` in `region`.
+    pub fn new(access_key: &str, secret_key: &str, region: &str, service: &str) -> Self {
+        AwsSigV4 {
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+            session_token: None,
+            region: region.to_string(),
+            service: service.to_string(),
+        }
+    }
+
+    /// Adds the session token of temporary credentials.
+    pub fn session_token(mut self, token: &str) -> Self {
+        self.session_token = Some(token.to_string());
+        self
+    }
+
+    fn sign_at(&self, request: &mut HttpRequest, unix_time: u64) -> Result<(), &'static str> {
+        let amz_date = format_amz_date(unix_time);
+        let date = &amz_date[..8];
+        let payload_hash = to_hex(&Sha256::digest(&request.body));
+
+        if !request.headers.contains_key("host") {
+            let host = request
+                .host
+                .strip_prefix("https://")
+                .or_else(|| request.host.strip_prefix("http://"))
+                .unwrap_or(&request.host);
+            let host = host.split('/').next().unwrap_or(host).to_string();
+            request.headers.insert("Host", &host);
+        }
+        request.headers.insert("X-Amz-Date", &amz_date);
+        if self.service == "s3" {
+            request
+                .headers
+                .insert("X-Amz-Content-Sha256", &payload_hash);
+        }
+        if let Some(token) = &self.session_token {
+            request.headers.insert("X-Amz-Security-Token", token);
+        }
+
+        let target = request.target();
+        let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+        let (canonical_headers, signed_headers) = canonical_headers(request);
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            request.method.to_str(),
+            canonical_uri(path, self.service != "s3"),
+            canonical_query(query),
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            to_hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut key = hmac(
+            format!("AWS4{}", self.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        for part in [self.region.as_str(), self.service.as_str(), "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        let signature = to_hex(&hmac(&key, string_to_sign.as_bytes()));
+
+        request.headers.insert(
+            "Authorization",
+            &format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.access_key, scope, signed_headers, signature
+            ),
+        );
+        Ok(())
+    }
+}
+
+impl Signer for AwsSigV4 {
+    fn sign(&self, request: &mut HttpRequest) -> Result<(), &'static str> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| "Invalid system time")?
+            .as_secs();
+        self.sign_at(request, now)
+    }
+}
+
+/// How an [`HmacSigner`] writes the signature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureEncoding {
+    /// Lowercase hex.
+    Hex,
+    /// Standard base64 with padding.
+    Base64,
+}
+
+/// Signs the request body with HMAC-SHA256 and puts the result in a header,
+/// as most webhook receivers expect (e.g. `X-Hub-Signature-256: sha256=...`).
+#[derive(Clone)]
+pub struct HmacSigner {
+    /// Name of the header the signature goes in.
+    pub header: String,
+    /// Shared secret key.
+    pub secret: String,
+    /// Text placed before the signature, such as `sha256=`.
+    pub prefix: String,
+    /// How the signature is written.
+    pub encoding: SignatureEncoding,
+}
+
+impl fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacSigner")
+            .field("header", &self.header)
+            .field("prefix", &self.prefix)
+            .field("encoding", &self.encoding)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HmacSigner {
+    /// Creates a signer writing the hex signature of the body to `header`.
+    pub fn new(header: &str, secret: &str) -> Self {
+        HmacSigner {
+            header: header.to_string(),
+            secret: secret.to_string(),
+            prefix: String::new(),
+            encoding: SignatureEncoding::Hex,
+        }
+    }
+
+    /// Sets the text placed before the signature.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Sets how the signature is written.
+    pub fn encoding(mut self, encoding: SignatureEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+}
+
+impl Signer for HmacSigner {
+    fn sign(&self, request: &mut HttpRequest) -> Result<(), &'static str> {
+        let signature = hmac(self.secret.as_bytes(), &request.body);
+        let signature = match self.encoding {
+            SignatureEncoding::Hex => to_hex(&signature),
+            SignatureEncoding::Base64 => STANDARD.encode(signature),
+        };
+        request
+            .headers
+            .insert(&self.header, &format!("{}{}", self.prefix, signature));
+        Ok(())
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Percent-encodes everything but the RFC 3986 unreserved characters.
+pub(crate) fn uri_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let Ok(hex) = std::str::from_utf8(&bytes[i + 1..i + 3])
+            && let Ok(b) = u8::from_str_radix(hex, 16)
+        {
+            decoded.push(b);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Normalizes each path segment's encoding. Every service but S3 wants the
+/// already encoded path encoded once more.
+fn canonical_uri(path: &str, double_encode: bool) -> String {
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    let segments: Vec<String> = path
+        .split('/')
+        .map(|segment| {
+            let encoded = uri_encode(&percent_decode(segment));
+            if double_encode {
+                uri_encode(&encoded)
+            } else {
+                encoded
+            }
+        })
+        .collect();
+    segments.join("/")
+}
+
+/// Sorts and re-encodes the query parameters.
+fn canonical_query(query: &str) -> String {
+    let mut params: Vec<(String, String)> = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (k, v) = p.split_once('=').unwrap_or((p, ""));
+            (
+                uri_encode(&percent_decode(k)),
+                uri_encode(&percent_decode(v)),
+            )
+        })
+        .collect();
+    params.sort();
+    let params: Vec<String> = params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    params.join("&")
+}
+
+/// Returns the canonical header block and the signed header list.
+fn canonical_headers(request: &HttpRequest) -> (String, String) {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for (k, v) in &request.headers {
+        let name = k.to_lowercase();
+        if UNSIGNED_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        let value = v.split_whitespace().collect::<Vec<_>>().join(" ");
+        match headers.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => {
+                existing.push(',');
+                existing.push_str(&value);
+            }
+            None => headers.push((name, value)),
+        }
+    }
+    headers.sort_by(|a, b| a.0.cmp(&b.0));
+    let block: String = headers
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v))
+        .collect();
+    let names: Vec<&str> = headers.iter().map(|(k, _)| k.as_str()).collect();
+    (block, names.join(";"))
+}
+
+/// Formats a unix time as `YYYYMMDDTHHMMSSZ`.
+fn format_amz_date(unix_time: u64) -> String {
+    let days = (unix_time / 86400) as i64;
+    let secs = unix_time % 86400;
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpMethod;
+
+    #[test]
+    fn test_amz_date() {
+        assert_eq!(format_amz_date(1440938160), "20150830T123600Z");
+        assert_eq!(format_amz_date(0), "19700101T000000Z");
+        assert_eq!(format_amz_date(951782400), "20000229T000000Z");
+    }
+
+    #[test]
+    fn test_sigv4_iam_example() {
+        // Example from the AWS Signature Version 4 documentation
+        let mut request = HttpRequest::new(HttpMethod::Get, "iam.amazonaws.com", "/");
+        request
+            .args
+            .push(("Version".to_string(), "2010-05-08".to_string()));
+        request
+            .args
+            .push(("Action".to_string(), "ListUsers".to_string()));
+        request.headers.insert(
+            "Content-Type",
+            "application/x-www-form-urlencoded; charset=utf-8",
+        );
+        request.headers.insert("User-Agent", "Sip/test");
+        let signer = AwsSigV4::new(
+            "AKIDEXAMPLE",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "us-east-1",
+            "iam",
+        );
+        signer.sign_at(&mut request, 1440938160).unwrap();
+        assert_eq!(
+            request.headers.get("authorization").unwrap(),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    #[test]
+    fn test_canonical_parts() {
+        assert_eq!(canonical_query("b=2&a=%7e&a=1&c"), "a=1&a=~&b=2&c=");
+        assert_eq!(
+            canonical_uri("/my bucket/a%20b", false),
+            "/my%20bucket/a%20b"
+        );
+        assert_eq!(canonical_uri("/a b", true), "/a%2520b");
+        assert_eq!(canonical_uri("", true), "/");
+    }
+
+    #[test]
+    fn test_hmac_signer() {
+        let mut request = HttpRequest::new(HttpMethod::Post, "localhost", "/hook");
+        request.body = b"Hello, World!".to_vec();
+        HmacSigner::new("X-Hub-Signature-256", "It's a Secret to Everybody")
+            .prefix("sha256=")
+            .sign(&mut request)
+            .unwrap();
+        // Example from the GitHub webhook documentation
+        assert_eq!(
+            request.headers.get("x-hub-signature-256").unwrap(),
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+        );
+
+        HmacSigner::new("X-Signature", "key")
+            .encoding(SignatureEncoding::Base64)
+            .sign(&mut request)
+            .unwrap();
+        assert!(request.headers.get("x-signature").unwrap().ends_with('='));
+    }
+}
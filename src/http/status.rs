@@ -10,210 +10,393 @@
 /// to variants that make them easier to work with in Rust.
 ///
 /// Use [`HttpStatus::from_u16`] to convert from raw codes,
-/// and [`HttpStatus::to_string`] to get the standard reason phrase.
+/// and [`HttpStatus::as_str`] to get the standard reason phrase.
 #[derive(Clone, Copy, Debug)]
 pub enum HttpStatus {
+    // 1xx Informational
+    /// `100 Continue`
+    Continue,
+    /// `101 Switching Protocols`
+    SwitchingProtocols,
+    /// `102 Processing`
+    Processing,
+    /// `103 Early Hints`
+    EarlyHints,
+
     // 2xx Success
-    OK = 200,
-    Created = 201,
-    Accepted = 202,
-    NonAuthoritativeInformation = 203,
-    NoContent = 204,
-    ResetContent = 205,
-    PartialContent = 206,
+    /// `200 OK`
+    OK,
+    /// `201 Created`
+    Created,
+    /// `202 Accepted`
+    Accepted,
+    /// `203 Non Authoritative Information`
+    NonAuthoritativeInformation,
+    /// `204 No Content`
+    NoContent,
+    /// `205 Reset Content`
+    ResetContent,
+    /// `206 Partial Content`
+    PartialContent,
 
     // 3xx Redirection
-    MultipleChoices = 300,
-    MovedPermanently = 301,
-    Found = 302, // (Renamed from MovedTemporarily)
-    SeeOther = 303,
-    NotModified = 304,
-    UseProxy = 305,
-    TemporaryRedirect = 307,
-    PermanentRedirect = 308,
+    /// `300 Multiple Choices`
+    MultipleChoices,
+    /// `301 Moved Permanently`
+    MovedPermanently,
+    /// `302 Found`
+    Found, // (Renamed from MovedTemporarily)
+    /// `303 See Other`
+    SeeOther,
+    /// `304 Not Modified`
+    NotModified,
+    /// `305 Use Proxy`
+    UseProxy,
+    /// `307 Temporary Redirect`
+    TemporaryRedirect,
+    /// `308 Permanent Redirect`
+    PermanentRedirect,
 
     // 4xx Client Error
-    BadRequest = 400,
-    Unauthorized = 401,
-    PaymentRequired = 402,
-    Forbidden = 403,
-    NotFound = 404,
-    MethodNotAllowed = 405,
-    NotAcceptable = 406,
-    ProxyAuthenticationRequired = 407,
-    RequestTimeout = 408,
-    Conflict = 409,
-    Gone = 410,
-    LengthRequired = 411,
-    PreconditionFailed = 412,
-    PayloadTooLarge = 413,
-    URITooLong = 414,
-    UnsupportedMediaType = 415,
-    RangeNotSatisfiable = 416,
-    ExpectationFailed = 417,
-    IAmATeapot = 418,
-    MisdirectedRequest = 421,
-    UnprocessableEntity = 422,
-    Locked = 423,
-    FailedDependency = 424,
-    TooEarly = 425,
-    UpgradeRequired = 426,
-    PreconditionRequired = 428,
-    TooManyRequests = 429,
-    RequestHeaderFieldsTooLarge = 431,
+    /// `400 Bad Request`
+    BadRequest,
+    /// `401 Unauthorized`
+    Unauthorized,
+    /// `402 Payment Required`
+    PaymentRequired,
+    /// `403 Forbidden`
+    Forbidden,
+    /// `404 Not Found`
+    NotFound,
+    /// `405 Method Not Allowed`
+    MethodNotAllowed,
+    /// `406 Not Acceptable`
+    NotAcceptable,
+    /// `407 Proxy Authentication Required`
+    ProxyAuthenticationRequired,
+    /// `408 Request Timeout`
+    RequestTimeout,
+    /// `409 Conflict`
+    Conflict,
+    /// `410 Gone`
+    Gone,
+    /// `411 Length Required`
+    LengthRequired,
+    /// `412 Precondition Failed`
+    PreconditionFailed,
+    /// `413 Payload Too Large`
+    PayloadTooLarge,
+    /// `414 URI Too Long`
+    URITooLong,
+    /// `415 Unsupported Media Type`
+    UnsupportedMediaType,
+    /// `416 Range Not Satisfiable`
+    RangeNotSatisfiable,
+    /// `417 Expectation Failed`
+    ExpectationFailed,
+    /// `418 I'm a teapot`
+    IAmATeapot,
+    /// `421 Misdirected Request`
+    MisdirectedRequest,
+    /// `422 Unprocessable Entity`
+    UnprocessableEntity,
+    /// `423 Locked`
+    Locked,
+    /// `424 Failed Dependency`
+    FailedDependency,
+    /// `425 Too Early`
+    TooEarly,
+    /// `426 Upgrade Required`
+    UpgradeRequired,
+    /// `428 Precondition Required`
+    PreconditionRequired,
+    /// `429 Too Many Requests`
+    TooManyRequests,
+    /// `431 Request Header Fields Too Large`
+    RequestHeaderFieldsTooLarge,
 
     // 5xx Server Error
-    InternalServerError = 500,
-    NotImplemented = 501,
-    BadGateway = 502,
-    ServiceUnavailable = 503,
-    GatewayTimeout = 504,
-    HTTPVersionNotSupported = 505,
-    VariantAlsoNegotiates = 506,
-    InsufficientStorage = 507,
-    LoopDetected = 508,
-    NotExtended = 510,
-    NetworkAuthenticationRequired = 511,
+    /// `500 Internal Server Error`
+    InternalServerError,
+    /// `501 Not Implemented`
+    NotImplemented,
+    /// `502 Bad Gateway`
+    BadGateway,
+    /// `503 Service Unavailable`
+    ServiceUnavailable,
+    /// `504 Gateway Timeout`
+    GatewayTimeout,
+    /// `505 HTTP Version Not Supported`
+    HTTPVersionNotSupported,
+    /// `506 Variant Also Negotiates`
+    VariantAlsoNegotiates,
+    /// `507 Insufficient Storage`
+    InsufficientStorage,
+    /// `508 Loop Detected`
+    LoopDetected,
+    /// `510 Not Extended`
+    NotExtended,
+    /// `511 Network Authentication Required`
+    NetworkAuthenticationRequired,
+
+    /// Any other code, such as vendor specific ones (e.g. Cloudflare's 520).
+    Other(u16),
 }
 
 impl HttpStatus {
     /// Attempts to convert a `u16` status code into an `HttpStatus` enum variant.
     ///
-    /// Returns an error if the code is not recognized.
+    /// Unknown codes become [`HttpStatus::Other`]. Returns an error if the code
+    /// is not a three digit number.
     pub fn from_u16(status: u16) -> Result<HttpStatus, &'static str> {
-        match status {
-            200 => Ok(HttpStatus::OK),
-            201 => Ok(HttpStatus::Created),
-            202 => Ok(HttpStatus::Accepted),
-            203 => Ok(HttpStatus::NonAuthoritativeInformation),
-            204 => Ok(HttpStatus::NoContent),
-            205 => Ok(HttpStatus::ResetContent),
-            206 => Ok(HttpStatus::PartialContent),
-
-            300 => Ok(HttpStatus::MultipleChoices),
-            301 => Ok(HttpStatus::MovedPermanently),
-            302 => Ok(HttpStatus::Found),
-            303 => Ok(HttpStatus::SeeOther),
-            304 => Ok(HttpStatus::NotModified),
-            305 => Ok(HttpStatus::UseProxy),
-            307 => Ok(HttpStatus::TemporaryRedirect),
-            308 => Ok(HttpStatus::PermanentRedirect),
-
-            400 => Ok(HttpStatus::BadRequest),
-            401 => Ok(HttpStatus::Unauthorized),
-            402 => Ok(HttpStatus::PaymentRequired),
-            403 => Ok(HttpStatus::Forbidden),
-            404 => Ok(HttpStatus::NotFound),
-            405 => Ok(HttpStatus::MethodNotAllowed),
-            406 => Ok(HttpStatus::NotAcceptable),
-            407 => Ok(HttpStatus::ProxyAuthenticationRequired),
-            408 => Ok(HttpStatus::RequestTimeout),
-            409 => Ok(HttpStatus::Conflict),
-            410 => Ok(HttpStatus::Gone),
-            411 => Ok(HttpStatus::LengthRequired),
-            412 => Ok(HttpStatus::PreconditionFailed),
-            413 => Ok(HttpStatus::PayloadTooLarge),
-            414 => Ok(HttpStatus::URITooLong),
-            415 => Ok(HttpStatus::UnsupportedMediaType),
-            416 => Ok(HttpStatus::RangeNotSatisfiable),
-            417 => Ok(HttpStatus::ExpectationFailed),
-            418 => Ok(HttpStatus::IAmATeapot),
-            421 => Ok(HttpStatus::MisdirectedRequest),
-            422 => Ok(HttpStatus::UnprocessableEntity),
-            423 => Ok(HttpStatus::Locked),
-            424 => Ok(HttpStatus::FailedDependency),
-            425 => Ok(HttpStatus::TooEarly),
-            426 => Ok(HttpStatus::UpgradeRequired),
-            428 => Ok(HttpStatus::PreconditionRequired),
-            429 => Ok(HttpStatus::TooManyRequests),
-            431 => Ok(HttpStatus::RequestHeaderFieldsTooLarge),
-
-            500 => Ok(HttpStatus::InternalServerError),
-            501 => Ok(HttpStatus::NotImplemented),
-            502 => Ok(HttpStatus::BadGateway),
-            503 => Ok(HttpStatus::ServiceUnavailable),
-            504 => Ok(HttpStatus::GatewayTimeout),
-            505 => Ok(HttpStatus::HTTPVersionNotSupported),
-            506 => Ok(HttpStatus::VariantAlsoNegotiates),
-            507 => Ok(HttpStatus::InsufficientStorage),
-            508 => Ok(HttpStatus::LoopDetected),
-            510 => Ok(HttpStatus::NotExtended),
-            511 => Ok(HttpStatus::NetworkAuthenticationRequired),
-
-            _ => Err("Invalid HTTP status"),
+        if !(100..=999).contains(&status) {
+            return Err("Invalid HTTP status");
+        }
+        Ok(STATUSES
+            .iter()
+            .find(|(_, code, _)| *code == status)
+            .map_or(HttpStatus::Other(status), |(known, _, _)| *known))
+    }
+
+    /// Returns the table entry for this status, if it has one.
+    fn known(&self) -> Option<&'static (HttpStatus, u16, &'static str)> {
+        match self {
+            HttpStatus::Other(code) => STATUSES.iter().find(|(_, known, _)| known == code),
+            _ => {
+                let variant = std::mem::discriminant(self);
+                STATUSES
+                    .iter()
+                    .find(|(known, _, _)| std::mem::discriminant(known) == variant)
+            }
         }
     }
 
+    /// Returns the numeric code.
     pub fn as_num(&self) -> usize {
-        *self as usize
+        match self {
+            HttpStatus::Other(code) => *code as usize,
+            _ => self.known().expect("named statuses are in STATUSES").1 as usize,
+        }
     }
 
+    /// Returns true for 2xx codes, like [`is_success`](Self::is_success).
     pub fn is_ok(&self) -> bool {
         self.as_num() >= 200 && self.as_num() <= 299
     }
 
+    /// Returns true for 1xx codes.
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.as_num())
+    }
+
+    /// Returns true for 2xx codes.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.as_num())
+    }
+
+    /// Returns true for 3xx codes.
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.as_num())
+    }
+
+    /// Returns true for 4xx codes.
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.as_num())
+    }
+
+    /// Returns true for 5xx codes.
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.as_num())
+    }
+
+    /// Returns true for interim 1xx responses that are followed by the
+    /// final response on the same connection.
+    ///
+    /// `101 Switching Protocols` is excluded because it ends the HTTP exchange.
+    pub fn is_interim(&self) -> bool {
+        self.as_num() < 200 && *self != HttpStatus::SwitchingProtocols
+    }
+
+    /// Returns true for statuses that usually mean "try again later":
+    /// 408, 429, 502, 503 and 504.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.as_num(), 408 | 429 | 502 | 503 | 504)
+    }
+
     /// Returns the standard reason phrase for this status code.
     ///
-    /// For example: `HttpStatus::OK.to_string()` returns `"OK"`.
-    pub fn as_str(&self) -> &str {
-        match self {
-            HttpStatus::OK => "OK",
-            HttpStatus::Created => "Created",
-            HttpStatus::Accepted => "Accepted",
-            HttpStatus::NonAuthoritativeInformation => "Non Authoritative Information",
-            HttpStatus::NoContent => "No Content",
-            HttpStatus::ResetContent => "Reset Content",
-            HttpStatus::PartialContent => "Partial Content",
-
-            HttpStatus::MultipleChoices => "Multiple Choices",
-            HttpStatus::MovedPermanently => "Moved Permanently",
-            HttpStatus::Found => "Found",
-            HttpStatus::SeeOther => "See Other",
-            HttpStatus::NotModified => "Not Modified",
-            HttpStatus::UseProxy => "Use Proxy",
-            HttpStatus::TemporaryRedirect => "Temporary Redirect",
-            HttpStatus::PermanentRedirect => "Permanent Redirect",
-
-            HttpStatus::BadRequest => "Bad Request",
-            HttpStatus::Unauthorized => "Unauthorized",
-            HttpStatus::PaymentRequired => "Payment Required",
-            HttpStatus::Forbidden => "Forbidden",
-            HttpStatus::NotFound => "Not Found",
-            HttpStatus::MethodNotAllowed => "Method Not Allowed",
-            HttpStatus::NotAcceptable => "Not Acceptable",
-            HttpStatus::ProxyAuthenticationRequired => "Proxy Authentication Required",
-            HttpStatus::RequestTimeout => "Request Timeout",
-            HttpStatus::Conflict => "Conflict",
-            HttpStatus::Gone => "Gone",
-            HttpStatus::LengthRequired => "Length Required",
-            HttpStatus::PreconditionFailed => "Precondition Failed",
-            HttpStatus::PayloadTooLarge => "Payload Too Large",
-            HttpStatus::URITooLong => "URI Too Long",
-            HttpStatus::UnsupportedMediaType => "Unsupported Media Type",
-            HttpStatus::RangeNotSatisfiable => "Range Not Satisfiable",
-            HttpStatus::ExpectationFailed => "Expectation Failed",
-            HttpStatus::IAmATeapot => "I'm a teapot",
-            HttpStatus::MisdirectedRequest => "Misdirected Request",
-            HttpStatus::UnprocessableEntity => "Unprocessable Entity",
-            HttpStatus::Locked => "Locked",
-            HttpStatus::FailedDependency => "Failed Dependency",
-            HttpStatus::TooEarly => "Too Early",
-            HttpStatus::UpgradeRequired => "Upgrade Required",
-            HttpStatus::PreconditionRequired => "Precondition Required",
-            HttpStatus::TooManyRequests => "Too Many Requests",
-            HttpStatus::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
-
-            HttpStatus::InternalServerError => "Internal Server Error",
-            HttpStatus::NotImplemented => "Not Implemented",
-            HttpStatus::BadGateway => "Bad Gateway",
-            HttpStatus::ServiceUnavailable => "Service Unavailable",
-            HttpStatus::GatewayTimeout => "Gateway Timeout",
-            HttpStatus::HTTPVersionNotSupported => "HTTP Version Not Supported",
-            HttpStatus::VariantAlsoNegotiates => "Variant Also Negotiates",
-            HttpStatus::InsufficientStorage => "Insufficient Storage",
-            HttpStatus::LoopDetected => "Loop Detected",
-            HttpStatus::NotExtended => "Not Extended",
-            HttpStatus::NetworkAuthenticationRequired => "Network Authentication Required",
+    /// For example: `HttpStatus::OK.as_str()` returns `"OK"`.
+    /// [`HttpStatus::Other`] codes only get the name of their class.
+    pub fn as_str(&self) -> &'static str {
+        if let Some((_, _, reason)) = self.known() {
+            return reason;
+        }
+        match self.as_num() / 100 {
+            1 => "Informational",
+            2 => "Success",
+            3 => "Redirection",
+            4 => "Client Error",
+            5 => "Server Error",
+            _ => "Unknown",
+        }
+    }
+}
+
+/// Compares codes, so `Other(404)` equals `NotFound`.
+impl PartialEq for HttpStatus {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_num() == other.as_num()
+    }
+}
+
+impl Eq for HttpStatus {}
+
+/// The named statuses with their code and reason phrase.
+const STATUSES: &[(HttpStatus, u16, &str)] = &[
+    (HttpStatus::Continue, 100, "Continue"),
+    (HttpStatus::SwitchingProtocols, 101, "Switching Protocols"),
+    (HttpStatus::Processing, 102, "Processing"),
+    (HttpStatus::EarlyHints, 103, "Early Hints"),
+    (HttpStatus::OK, 200, "OK"),
+    (HttpStatus::Created, 201, "Created"),
+    (HttpStatus::Accepted, 202, "Accepted"),
+    (
+        HttpStatus::NonAuthoritativeInformation,
+        203,
+        "Non Authoritative Information",
+    ),
+    (HttpStatus::NoContent, 204, "No Content"),
+    (HttpStatus::ResetContent, 205, "Reset Content"),
+    (HttpStatus::PartialContent, 206, "Partial Content"),
+    (HttpStatus::MultipleChoices, 300, "Multiple Choices"),
+    (HttpStatus::MovedPermanently, 301, "Moved Permanently"),
+    (HttpStatus::Found, 302, "Found"),
+    (HttpStatus::SeeOther, 303, "See Other"),
+    (HttpStatus::NotModified, 304, "Not Modified"),
+    (HttpStatus::UseProxy, 305, "Use Proxy"),
+    (HttpStatus::TemporaryRedirect, 307, "Temporary Redirect"),
+    (HttpStatus::PermanentRedirect, 308, "Permanent Redirect"),
+    (HttpStatus::BadRequest, 400, "Bad Request"),
+    (HttpStatus::Unauthorized, 401, "Unauthorized"),
+    (HttpStatus::PaymentRequired, 402, "Payment Required"),
+    (HttpStatus::Forbidden, 403, "Forbidden"),
+    (HttpStatus::NotFound, 404, "Not Found"),
+    (HttpStatus::MethodNotAllowed, 405, "Method Not Allowed"),
+    (HttpStatus::NotAcceptable, 406, "Not Acceptable"),
+    (
+        HttpStatus::ProxyAuthenticationRequired,
+        407,
+        "Proxy Authentication Required",
+    ),
+    (HttpStatus::RequestTimeout, 408, "Request Timeout"),
+    (HttpStatus::Conflict, 409, "Conflict"),
+    (HttpStatus::Gone, 410, "Gone"),
+    (HttpStatus::LengthRequired, 411, "Length Required"),
+    (HttpStatus::PreconditionFailed, 412, "Precondition Failed"),
+    (HttpStatus::PayloadTooLarge, 413, "Payload Too Large"),
+    (HttpStatus::URITooLong, 414, "URI Too Long"),
+    (
+        HttpStatus::UnsupportedMediaType,
+        415,
+        "Unsupported Media Type",
+    ),
+    (
+        HttpStatus::RangeNotSatisfiable,
+        416,
+        "Range Not Satisfiable",
+    ),
+    (HttpStatus::ExpectationFailed, 417, "Expectation Failed"),
+    (HttpStatus::IAmATeapot, 418, "I'm a teapot"),
+    (HttpStatus::MisdirectedRequest, 421, "Misdirected Request"),
+    (HttpStatus::UnprocessableEntity, 422, "Unprocessable Entity"),
+    (HttpStatus::Locked, 423, "Locked"),
+    (HttpStatus::FailedDependency, 424, "Failed Dependency"),
+    (HttpStatus::TooEarly, 425, "Too Early"),
+    (HttpStatus::UpgradeRequired, 426, "Upgrade Required"),
+    (
+        HttpStatus::PreconditionRequired,
+        428,
+        "Precondition Required",
+    ),
+    (HttpStatus::TooManyRequests, 429, "Too Many Requests"),
+    (
+        HttpStatus::RequestHeaderFieldsTooLarge,
+        431,
+        "Request Header Fields Too Large",
+    ),
+    (
+        HttpStatus::InternalServerError,
+        500,
+        "Internal Server Error",
+    ),
+    (HttpStatus::NotImplemented, 501, "Not Implemented"),
+    (HttpStatus::BadGateway, 502, "Bad Gateway"),
+    (HttpStatus::ServiceUnavailable, 503, "Service Unavailable"),
+    (HttpStatus::GatewayTimeout, 504, "Gateway Timeout"),
+    (
+        HttpStatus::HTTPVersionNotSupported,
+        505,
+        "HTTP Version Not Supported",
+    ),
+    (
+        HttpStatus::VariantAlsoNegotiates,
+        506,
+        "Variant Also Negotiates",
+    ),
+    (HttpStatus::InsufficientStorage, 507, "Insufficient Storage"),
+    (HttpStatus::LoopDetected, 508, "Loop Detected"),
+    (HttpStatus::NotExtended, 510, "Not Extended"),
+    (
+        HttpStatus::NetworkAuthenticationRequired,
+        511,
+        "Network Authentication Required",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_u16_as_num() {
+        for code in 100..=999 {
+            let status = HttpStatus::from_u16(code).unwrap();
+            assert_eq!(status.as_num(), code as usize);
         }
+        for (status, code, _) in STATUSES {
+            let parsed = HttpStatus::from_u16(*code).unwrap();
+            assert_eq!(
+                std::mem::discriminant(&parsed),
+                std::mem::discriminant(status)
+            );
+        }
+        assert!(matches!(
+            HttpStatus::from_u16(404),
+            Ok(HttpStatus::NotFound)
+        ));
+        assert!(HttpStatus::from_u16(99).is_err());
+        assert!(HttpStatus::from_u16(1000).is_err());
+    }
+
+    #[test]
+    fn test_unknown_codes() {
+        let status = HttpStatus::from_u16(520).unwrap();
+        assert_eq!(status, HttpStatus::Other(520));
+        assert_eq!(HttpStatus::Other(404), HttpStatus::NotFound);
+        assert_eq!(HttpStatus::Other(404).as_str(), "Not Found");
+        assert!(status.is_server_error());
+        assert!(!status.is_client_error());
+        assert!(HttpStatus::from_u16(299).unwrap().is_success());
+        assert!(HttpStatus::from_u16(420).unwrap().is_client_error());
+        assert!(HttpStatus::MovedPermanently.is_redirect());
+        assert!(HttpStatus::EarlyHints.is_informational());
+    }
+
+    #[test]
+    fn test_retryable() {
+        assert!(HttpStatus::TooManyRequests.is_retryable());
+        assert!(HttpStatus::ServiceUnavailable.is_retryable());
+        assert!(!HttpStatus::InternalServerError.is_retryable());
+        assert!(!HttpStatus::Other(599).is_retryable());
     }
 }
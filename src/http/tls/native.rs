@@ -0,0 +1,153 @@
+//! TLS through `native-tls`: OpenSSL on Linux, Secure Transport on macOS and
+//! SChannel on Windows.
+
+use std::io::{Read, Write};
+
+#[cfg(not(feature = "rustls"))]
+use native_tls::HandshakeError;
+use native_tls::{Certificate, Identity, Protocol, TlsConnector, TlsStream};
+
+#[cfg(not(feature = "rustls"))]
+use super::TlsBackend;
+use super::{CertificateInfo, ClientCert, TlsConfig, TlsInfo, TlsVersion};
+#[cfg(all(feature = "async-native-tls", not(feature = "async-rustls")))]
+use crate::http::async_client::AsyncStream;
+#[cfg(not(feature = "rustls"))]
+use crate::http::brew::StreamRW;
+
+#[cfg(not(feature = "rustls"))]
+pub(crate) struct NativeTls;
+
+#[cfg(not(feature = "rustls"))]
+impl TlsBackend for NativeTls {
+    fn connect(
+        &self,
+        config: &TlsConfig,
+        host: &str,
+        stream: Box<dyn StreamRW>,
+    ) -> Result<(Box<dyn StreamRW>, TlsInfo), &'static str> {
+        let connector = connector(config)?;
+        let hostname = config.server_name(host);
+        let stream = connector.connect(hostname, stream).map_err(|e| match e {
+            HandshakeError::Failure(_) => "TLS handshake failed",
+            HandshakeError::WouldBlock(_) => "TLS handshake timed out",
+        })?;
+        let info = tls_info(&stream);
+        Ok((Box::new(stream), info))
+    }
+}
+
+/// Performs the handshake over an async stream.
+#[cfg(all(feature = "async-native-tls", not(feature = "async-rustls")))]
+pub(crate) async fn connect_async(
+    config: &TlsConfig,
+    host: &str,
+    stream: Box<dyn AsyncStream>,
+) -> Result<(Box<dyn AsyncStream>, TlsInfo), &'static str> {
+    let connector = tokio_native_tls::TlsConnector::from(connector(config)?);
+    let hostname = config.server_name(host);
+    let stream = connector
+        .connect(hostname, stream)
+        .await
+        .map_err(|_| "TLS handshake failed")?;
+    let info = tls_info(stream.get_ref());
+    Ok((Box::new(stream), info))
+}
+
+/// Builds a connector from the settings, reading the certificate files.
+fn connector(config: &TlsConfig) -> Result<TlsConnector, &'static str> {
+    let mut builder = TlsConnector::builder();
+    for pem in config.ca_certificates()? {
+        let cert = Certificate::from_pem(&pem).map_err(|_| "Invalid CA certificate")?;
+        builder.add_root_certificate(cert);
+    }
+    if let Some(client_cert) = &config.client_cert {
+        builder.identity(identity(client_cert)?);
+    }
+    if let Some(min) = config.min_version {
+        if min == TlsVersion::Tls13 {
+            return Err("TLS 1.3 can't be required with this TLS backend");
+        }
+        builder.min_protocol_version(protocol(min));
+    }
+    if let Some(max) = config.max_version {
+        if config.min_version.is_some_and(|min| min > max) {
+            return Err("Minimum TLS version is above the maximum");
+        }
+        builder.max_protocol_version(protocol(max));
+    }
+    builder.danger_accept_invalid_certs(config.insecure);
+    builder.danger_accept_invalid_hostnames(config.insecure);
+    builder.request_alpns(&["http/1.1"]);
+    builder.build().map_err(|_| "SSL error")
+}
+
+/// native-tls has no TLS 1.3 constant, it is always the highest version.
+fn protocol(version: TlsVersion) -> Option<Protocol> {
+    match version {
+        TlsVersion::Tls10 => Some(Protocol::Tlsv10),
+        TlsVersion::Tls11 => Some(Protocol::Tlsv11),
+        TlsVersion::Tls12 => Some(Protocol::Tlsv12),
+        TlsVersion::Tls13 => None,
+    }
+}
+
+fn identity(client_cert: &ClientCert) -> Result<Identity, &'static str> {
+    match client_cert {
+        ClientCert::Pem { cert, key } => {
+            let cert = std::fs::read(cert).map_err(|_| "Error reading client certificate")?;
+            let key = std::fs::read(key).map_err(|_| "Error reading client key")?;
+            Identity::from_pkcs8(&cert, &key).map_err(|_| "Invalid client certificate")
+        }
+        ClientCert::Pkcs12 { file, password } => {
+            let der = std::fs::read(file).map_err(|_| "Error reading client certificate")?;
+            Identity::from_pkcs12(&der, password).map_err(|_| "Invalid client certificate")
+        }
+    }
+}
+
+/// native-tls has no portable way to get the version, the cipher or the
+/// chain, so the info is marked `partial`.
+fn tls_info<S: Read + Write>(stream: &TlsStream<S>) -> TlsInfo {
+    let alpn = stream
+        .negotiated_alpn()
+        .ok()
+        .flatten()
+        .map(|alpn| String::from_utf8_lossy(&alpn).to_string());
+    let certificates = stream
+        .peer_certificate()
+        .ok()
+        .flatten()
+        .and_then(|cert| cert.to_der().ok())
+        .and_then(|der| CertificateInfo::from_der(&der).ok())
+        .into_iter()
+        .collect();
+    TlsInfo {
+        protocol: None,
+        cipher: None,
+        alpn,
+        certificates,
+        partial: true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_connector() {
+        let mut config = TlsConfig::new();
+        config.insecure = true;
+        config.min_version = Some(TlsVersion::Tls12);
+        config.max_version = Some(TlsVersion::Tls13);
+        assert!(connector(&config).is_ok());
+
+        config.min_version = Some(TlsVersion::Tls13);
+        assert!(connector(&config).is_err());
+        config.min_version = None;
+        config.ca_files.push(PathBuf::from("/nonexistent/ca.pem"));
+        assert_eq!(connector(&config).err(), Some("Error reading CA file"));
+    }
+}
@@ -0,0 +1,245 @@
+//! TLS through `rustls`, which needs no system libraries.
+//!
+//! Roots come from `webpki-roots`, or from the system store with the
+//! `rustls-native-roots` feature. Only TLS 1.2 and 1.3 are available and
+//! client certificates must be PEM files.
+
+use std::fs;
+use std::sync::Arc;
+
+use ::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use ::rustls::crypto::{CryptoProvider, ring, verify_tls12_signature, verify_tls13_signature};
+use ::rustls::pki_types::pem::PemObject;
+use ::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use ::rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, ProtocolVersion, RootCertStore,
+    SignatureScheme, StreamOwned, SupportedProtocolVersion, version,
+};
+
+use super::{CertificateInfo, ClientCert, TlsBackend, TlsConfig, TlsInfo, TlsVersion};
+#[cfg(feature = "async-rustls")]
+use crate::http::async_client::AsyncStream;
+use crate::http::brew::StreamRW;
+
+pub(crate) struct Rustls;
+
+impl TlsBackend for Rustls {
+    fn connect(
+        &self,
+        config: &TlsConfig,
+        host: &str,
+        mut stream: Box<dyn StreamRW>,
+    ) -> Result<(Box<dyn StreamRW>, TlsInfo), &'static str> {
+        let hostname = config.server_name(host);
+        let name = ServerName::try_from(hostname.to_string()).map_err(|_| "Invalid server name")?;
+        let mut connection = ClientConnection::new(Arc::new(client_config(config)?), name)
+            .map_err(|_| "SSL error")?;
+        while connection.is_handshaking() {
+            connection
+                .complete_io(&mut stream)
+                .map_err(handshake_error)?;
+        }
+        let info = tls_info(&connection);
+        Ok((Box::new(StreamOwned::new(connection, stream)), info))
+    }
+}
+
+/// Performs the handshake over an async stream.
+#[cfg(feature = "async-rustls")]
+pub(crate) async fn connect_async(
+    config: &TlsConfig,
+    host: &str,
+    stream: Box<dyn AsyncStream>,
+) -> Result<(Box<dyn AsyncStream>, TlsInfo), &'static str> {
+    let hostname = config.server_name(host);
+    let name = ServerName::try_from(hostname.to_string()).map_err(|_| "Invalid server name")?;
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config(config)?));
+    let stream = connector
+        .connect(name, stream)
+        .await
+        .map_err(handshake_error)?;
+    let info = tls_info(stream.get_ref().1);
+    Ok((Box::new(stream), info))
+}
+
+/// Describes a failed handshake, telling certificate problems apart.
+fn handshake_error(error: std::io::Error) -> &'static str {
+    match error
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<::rustls::Error>())
+    {
+        Some(::rustls::Error::InvalidCertificate(_)) => "Invalid server certificate",
+        Some(_) => "TLS handshake failed",
+        None if error.kind() == std::io::ErrorKind::WouldBlock => "TLS handshake timed out",
+        None => "Connection closed during the TLS handshake",
+    }
+}
+
+fn client_config(config: &TlsConfig) -> Result<ClientConfig, &'static str> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&versions(config)?)
+        .map_err(|_| "SSL error")?;
+    let builder = if config.insecure {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAny(provider)))
+    } else {
+        builder.with_root_certificates(roots(config)?)
+    };
+    let mut client_config = match &config.client_cert {
+        None => builder.with_no_client_auth(),
+        Some(ClientCert::Pem { cert, key }) => {
+            let cert = fs::read(cert).map_err(|_| "Error reading client certificate")?;
+            let key = fs::read(key).map_err(|_| "Error reading client key")?;
+            let certs = CertificateDer::pem_slice_iter(&cert)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| "Invalid client certificate")?;
+            let key = PrivateKeyDer::from_pem_slice(&key).map_err(|_| "Invalid client key")?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|_| "Invalid client certificate")?
+        }
+        Some(ClientCert::Pkcs12 { .. }) => {
+            return Err("PKCS#12 client certificates need the native-tls backend");
+        }
+    };
+    client_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(client_config)
+}
+
+/// Older versions than TLS 1.2 are not implemented, a lower minimum is fine.
+fn versions(config: &TlsConfig) -> Result<Vec<&'static SupportedProtocolVersion>, &'static str> {
+    let min = config.min_version.unwrap_or(TlsVersion::Tls12);
+    let max = config.max_version.unwrap_or(TlsVersion::Tls13);
+    if min > max {
+        return Err("Minimum TLS version is above the maximum");
+    }
+    let versions: Vec<_> = [
+        (TlsVersion::Tls12, &version::TLS12),
+        (TlsVersion::Tls13, &version::TLS13),
+    ]
+    .into_iter()
+    .filter(|(v, _)| min <= *v && *v <= max)
+    .map(|(_, supported)| supported)
+    .collect();
+    if versions.is_empty() {
+        return Err("rustls only supports TLS 1.2 and 1.3");
+    }
+    Ok(versions)
+}
+
+fn roots(config: &TlsConfig) -> Result<RootCertStore, &'static str> {
+    let mut roots = RootCertStore::empty();
+    #[cfg(feature = "rustls-native-roots")]
+    roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+    #[cfg(not(feature = "rustls-native-roots"))]
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    for pem in config.ca_certificates()? {
+        let cert = CertificateDer::from_pem_slice(&pem).map_err(|_| "Invalid CA certificate")?;
+        roots.add(cert).map_err(|_| "Invalid CA certificate")?;
+    }
+    Ok(roots)
+}
+
+fn tls_info(connection: &ClientConnection) -> TlsInfo {
+    let protocol = connection.protocol_version().map(|v| match v {
+        ProtocolVersion::TLSv1_2 => "TLSv1.2".to_string(),
+        ProtocolVersion::TLSv1_3 => "TLSv1.3".to_string(),
+        other => format!("{:?}", other),
+    });
+    let cipher = connection
+        .negotiated_cipher_suite()
+        .map(|suite| format!("{:?}", suite.suite()));
+    let alpn = connection
+        .alpn_protocol()
+        .map(|alpn| String::from_utf8_lossy(alpn).to_string());
+    let certificates = connection
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|cert| CertificateInfo::from_der(cert).ok())
+        .collect();
+    TlsInfo {
+        protocol,
+        cipher,
+        alpn,
+        certificates,
+        partial: false,
+    }
+}
+
+/// Skips certificate checks for `--insecure`, signatures are still verified.
+#[derive(Debug)]
+struct AcceptAny(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAny {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, ::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, ::rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, ::rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions() {
+        let mut config = TlsConfig::new();
+        assert_eq!(versions(&config).unwrap().len(), 2);
+        config.min_version = Some(TlsVersion::Tls10);
+        config.max_version = Some(TlsVersion::Tls12);
+        assert_eq!(versions(&config).unwrap(), vec![&version::TLS12]);
+        config.max_version = Some(TlsVersion::Tls11);
+        assert!(versions(&config).is_err());
+
+        config.max_version = None;
+        config.client_cert = Some(ClientCert::Pkcs12 {
+            file: "id.p12".into(),
+            password: String::new(),
+        });
+        assert!(client_config(&config).is_err());
+        config.client_cert = None;
+        config.insecure = true;
+        assert!(client_config(&config).is_ok());
+    }
+}
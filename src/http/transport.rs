@@ -0,0 +1,230 @@
+//! The sockets requests are sent over: TCP, or a Unix domain socket for
+//! local daemons like Docker.
+//!
+//! TCP connections race the resolved addresses like RFC 8305 (Happy
+//! Eyeballs): a new attempt starts every 250ms, alternating IPv6 and IPv4,
+//! and the first to connect wins.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::brew::CONNECT_ERROR;
+
+/// Delay before the next address is tried if the previous one hasn't
+/// connected or failed yet.
+pub(crate) const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Restricts connections to one address family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpVersion {
+    /// IPv4 only.
+    V4,
+    /// IPv6 only.
+    V6,
+}
+
+impl IpVersion {
+    /// Returns true if `addr` belongs to this family.
+    pub fn matches(self, addr: &SocketAddr) -> bool {
+        match self {
+            IpVersion::V4 => addr.is_ipv4(),
+            IpVersion::V6 => addr.is_ipv6(),
+        }
+    }
+}
+
+pub(crate) enum Socket {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Socket {
+    /// Connects to the Unix domain socket at `path`.
+    pub(crate) fn unix(path: &Path) -> Result<Socket, &'static str> {
+        #[cfg(unix)]
+        return UnixStream::connect(path)
+            .map(Socket::Unix)
+            .map_err(|_| "Error connecting to unix socket");
+        #[cfg(not(unix))]
+        {
+            let _ = path;
+            Err("Unix sockets are not supported on this platform")
+        }
+    }
+
+    pub(crate) fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    /// Returns a handle to the same socket, used to change timeouts once the
+    /// stream has been wrapped in TLS.
+    pub(crate) fn try_clone(&self) -> io::Result<Socket> {
+        match self {
+            Socket::Tcp(stream) => stream.try_clone().map(Socket::Tcp),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.try_clone().map(Socket::Unix),
+        }
+    }
+}
+
+/// Connects to the first of `addrs` to answer, giving up after `timeout`.
+pub(crate) fn connect_any(
+    addrs: &[SocketAddr],
+    timeout: Duration,
+) -> Result<(TcpStream, SocketAddr), &'static str> {
+    let addrs = interleave(addrs);
+    if addrs.is_empty() {
+        return Err("No valid address found");
+    }
+    let deadline = Instant::now() + timeout;
+    let (tx, rx) = mpsc::channel();
+    let mut pending = addrs.into_iter();
+    let mut running = 0;
+    loop {
+        if let Some(addr) = pending.next() {
+            let tx = tx.clone();
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            running += 1;
+            thread::spawn(move || {
+                let result =
+                    TcpStream::connect_timeout(&addr, remaining.max(Duration::from_millis(1)));
+                let _ = tx.send((addr, result));
+            });
+        }
+        // Wait for an answer, or until it's time to start the next attempt
+        let wait = match pending.len() {
+            0 => deadline.saturating_duration_since(Instant::now()),
+            _ => CONNECTION_ATTEMPT_DELAY,
+        };
+        match rx.recv_timeout(wait) {
+            Ok((addr, Ok(stream))) => return Ok((stream, addr)),
+            Ok((_, Err(_))) => {
+                running -= 1;
+                if running == 0 && pending.len() == 0 {
+                    return Err(CONNECT_ERROR);
+                }
+            }
+            Err(_) if Instant::now() >= deadline => return Err(CONNECT_ERROR),
+            Err(_) => {}
+        }
+    }
+}
+
+/// Orders addresses alternating families, starting with the family of the
+/// first one, as RFC 8305 section 4 recommends.
+pub(crate) fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_v6 = addrs.first().is_some_and(|a| a.is_ipv6());
+    let (mut first, mut second): (Vec<SocketAddr>, Vec<SocketAddr>) =
+        addrs.iter().partition(|a| a.is_ipv6() == first_v6);
+    first.reverse();
+    second.reverse();
+    let mut ordered = Vec::with_capacity(addrs.len());
+    while !first.is_empty() || !second.is_empty() {
+        ordered.extend(first.pop());
+        ordered.extend(second.pop());
+    }
+    ordered
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{HttpMethod, HttpRequest};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_interleave() {
+        let addrs: Vec<SocketAddr> = ["[::1]:80", "[::2]:80", "[::3]:80", "10.0.0.1:80"]
+            .iter()
+            .map(|a| a.parse().unwrap())
+            .collect();
+        let ordered = interleave(&addrs);
+        assert_eq!(ordered, vec![addrs[0], addrs[3], addrs[1], addrs[2]]);
+    }
+
+    #[test]
+    fn test_connect_fallback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let good = listener.local_addr().unwrap();
+        // A port nobody listens on
+        let refused = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (_, addr) = connect_any(&[refused, good], Duration::from_secs(5)).unwrap();
+        assert_eq!(addr, good);
+        assert!(connect_any(&[refused], Duration::from_secs(1)).is_err());
+        assert!(connect_any(&[], Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_unix_socket() {
+        use std::os::unix::net::UnixListener;
+        let path = std::env::temp_dir().join(format!("sip-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !head.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buffer).unwrap();
+                head.extend_from_slice(&buffer[..n]);
+            }
+            tx.send(String::from_utf8(head).unwrap()).unwrap();
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n[]");
+        });
+
+        let mut request = HttpRequest::new(HttpMethod::Get, "localhost", "/containers/json");
+        request.headers.insert("Host", "localhost");
+        request.unix_socket = Some(path.clone());
+        let response = request.brew();
+        let _ = std::fs::remove_file(&path);
+        let response = response.unwrap();
+        assert_eq!(response.content, b"[]");
+        assert!(response.connection.remote_addr.is_none());
+        let head = rx.recv().unwrap();
+        assert!(head.starts_with("GET /containers/json HTTP/1.1\r\nHost: localhost\r\n"));
+    }
+}
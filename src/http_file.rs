@@ -0,0 +1,187 @@
+//! Reading `.http` request files.
+//!
+//! A file holds requests separated by `###` lines, the rest of the line
+//! names the request that follows. `@name = value` lines define variables
+//! used as `{name}` in later requests, and lines starting with `#` are
+//! comments:
+//!
+//! ```text
+//! @host = https://api.example.com
+//!
+//! ### list users
+//! GET {host}/users
+//! Accept: application/json
+//!
+//! ### create user
+//! POST {host}/users
+//! Content-Type: application/json
+//!
+//! {"name": "tea"}
+//! ```
+
+use std::collections::HashMap;
+
+use crate::http::HttpRequest;
+
+/// Parses the requests in a `.http` file, in order, with their names.
+///
+/// Unnamed requests get an empty name, blocks that aren't valid requests
+/// are skipped.
+///
+/// ```
+/// let requests = sip::parse_http_file("@id = 7\n### user\nGET localhost/users/{id}\n");
+/// assert_eq!(requests[0].0, "user");
+/// assert_eq!(requests[0].1.path, "/users/7");
+/// ```
+pub fn parse_http_file(content: &str) -> Vec<(String, HttpRequest)> {
+    let mut result = Vec::new();
+    let mut vars: HashMap<String, String> = HashMap::new();
+    let mut name = String::new();
+    let mut raw_request = String::new();
+
+    for line in content.lines() {
+        if let Some(next_name) = line.strip_prefix("###") {
+            push_request(&mut result, &name, &raw_request, &vars);
+            name = next_name.trim().to_string();
+            raw_request.clear();
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(var) = line.strip_prefix('@') {
+            if let Some((k, v)) = var.split_once('=') {
+                vars.insert(k.trim().to_string(), v.trim().to_string());
+            }
+            continue;
+        }
+        // Blank lines before the request line don't count
+        if raw_request.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        raw_request.push_str(line);
+        raw_request.push('\n');
+    }
+    push_request(&mut result, &name, &raw_request, &vars);
+    result
+}
+
+/// Substitutes the variables in `raw_request` and adds it if it parses.
+fn push_request(
+    result: &mut Vec<(String, HttpRequest)>,
+    name: &str,
+    raw_request: &str,
+    vars: &HashMap<String, String>,
+) {
+    if raw_request.trim().is_empty() {
+        return;
+    }
+    let mut raw_request = raw_request.to_string();
+    for (k, v) in vars {
+        raw_request = raw_request.replace(&format!("{{{}}}", k), v);
+    }
+    if let Ok(request) = HttpRequest::parse(raw_request) {
+        result.push((name.to_string(), request));
+    }
+}
+
+/// Writes requests back in the `.http` format, each after a `### name`
+/// line.
+///
+/// `Host` is left out when it matches the URL, as the parser adds it back,
+/// and so is `Content-Length`.
+pub fn to_http_file(requests: &[(String, HttpRequest)]) -> String {
+    let mut content = String::new();
+    for (name, request) in requests {
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        match name.as_str() {
+            "" => content.push_str("###\n"),
+            name => content.push_str(&format!("### {}\n", name)),
+        }
+        content.push_str(&format!("{} {}\n", request.method.to_str(), request.url()));
+        for (k, v) in written_headers(request) {
+            content.push_str(&format!("{}: {}\n", k, v));
+        }
+        if !request.body.is_empty() {
+            content.push('\n');
+            content.push_str(&String::from_utf8_lossy(&request.body));
+            content.push('\n');
+        }
+    }
+    content
+}
+
+/// Returns the headers worth writing out: all but `Content-Length` and a
+/// `Host` that matches the URL.
+fn written_headers(request: &HttpRequest) -> impl Iterator<Item = (&str, &str)> {
+    let authority = request.host.rsplit("://").next().unwrap_or_default();
+    request
+        .headers
+        .iter()
+        .map(|(k, v)| (&**k, v.as_str()))
+        .filter(move |(k, v)| match k.to_lowercase().as_str() {
+            "content-length" => false,
+            "host" => *v != authority,
+            _ => true,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpMethod;
+
+    #[test]
+    fn test_parse_http_file() {
+        let content = "@host = https://api.example.com\n\
+                       @broken\n\
+                       # a comment\n\
+                       GET {host}/health\n\
+                       \n\
+                       ### create user\n\
+                       \n\
+                       POST {host}/users HTTP/1.1\n\
+                       Content-Type: application/json\n\
+                       \n\
+                       {\"name\": \"tea\"}\n\
+                       ###\n";
+        let requests = parse_http_file(content);
+        assert_eq!(requests.len(), 2);
+
+        let (name, request) = &requests[0];
+        assert_eq!(name, "");
+        assert_eq!(request.method, HttpMethod::Get);
+        assert_eq!(
+            request.endpoint().unwrap(),
+            (true, "api.example.com".into(), 443)
+        );
+        assert_eq!(request.path, "/health");
+
+        let (name, request) = &requests[1];
+        assert_eq!(name, "create user");
+        assert_eq!(request.method, HttpMethod::Post);
+        assert_eq!(request.path, "/users");
+        assert_eq!(
+            request.headers.get("content-type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(request.body, b"{\"name\": \"tea\"}");
+    }
+
+    #[test]
+    fn test_to_http_file() {
+        let content = "### create user\n\
+                       POST https://api.example.com/users?v=2\n\
+                       Content-Type: application/json\n\
+                       \n\
+                       {\"name\": \"tea\"}\n\
+                       \n\
+                       ###\n\
+                       GET http://localhost:8080/\n\
+                       Host: example.com\n";
+        let requests = parse_http_file(content);
+        assert_eq!(to_http_file(&requests), content);
+    }
+}
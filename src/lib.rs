@@ -0,0 +1,28 @@
+//! Sip is a small HTTP/1.1 client, and the library behind the `Sip` command
+//! line tool.
+//!
+//! Build an [`HttpRequest`], set the fields you need (headers, body, TLS,
+//! proxy, retries...) and send it with [`HttpRequest::brew`], or use the
+//! fluent [`Client`]:
+//!
+//! ```no_run
+//! use sip::{HttpMethod, HttpRequest};
+//!
+//! let mut request = HttpRequest::new(HttpMethod::Get, "https://example.org", "/");
+//! request.headers.insert("Accept", "text/html");
+//! let response = request.brew()?;
+//! println!("{} {}", response.status.as_num(), response.reason);
+//! # Ok::<(), &'static str>(())
+//! ```
+//!
+//! Requests can also be read from `.http` files with [`parse_http_file`].
+//! With the `async` feature, [`http::async_client::send`] sends requests on
+//! tokio.
+//!
+//! Errors are `&'static str` messages meant to be shown to the user.
+
+pub mod http;
+mod http_file;
+
+pub use http::{Client, HttpHeaders, HttpMethod, HttpRequest, HttpResponse, HttpStatus};
+pub use http_file::{parse_http_file, to_http_file};
@@ -1,122 +1,891 @@
-mod http;
+mod curl;
 mod utils;
 
-use std::{collections::HashMap, env};
-use utils::{print_response, save_file};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io, io::Read, process, sync::Arc, time::Duration};
+use utils::{print_response, print_tls_info, save_file};
 
-use http::{HttpRequest, HttpResponse};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use curl::{parse_curl, to_curl};
+use serde_json::Value;
+use sip::http::{
+    Auth, AwsSigV4, BasicAuth, BearerAuth, ClientCert, CookieJar, DigestAuth, HmacSigner,
+    HostsResolver, HttpMethod, HttpRequest, HttpResponse, IpVersion, Proxy, Resolver, RetryPolicy,
+    Session, SignatureEncoding, Signer, StaticResolver, SystemResolver, TlsConfig, TlsVersion,
+    accept_encoding, env_no_proxy, no_proxy_matches, parse_connect_to, parse_no_proxy,
+    parse_resolve,
+};
+use sip::{parse_http_file, to_http_file};
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[cfg(feature = "config_file")]
-fn parse_http_file(content: String) -> Vec<(String, HttpRequest)> {
-    let mut result: Vec<(String, HttpRequest)> = Vec::new();
-    let mut vars: HashMap<String, String> = HashMap::new();
-    let mut raw_request = "".to_string();
-
-    for line in content.split("\n") {
-        if line.starts_with("###") && !raw_request.is_empty() {
-            for (k, v) in vars.iter() {
-                let k = format!("{{{}}}", k);
-                raw_request = raw_request.replace(&k, v);
+/// Bodies at least this big wait for `100 Continue` before being sent.
+const EXPECT_CONTINUE_SIZE: usize = 1024 * 1024;
+
+/// A small HTTP client.
+///
+/// Without a command the arguments are a request, so `Sip GET host/users`
+/// is `Sip request GET host/users`.
+#[derive(Parser, Debug)]
+#[command(name = "Sip", version = VERSION, arg_required_else_help = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send a request built from the command line
+    Request(RequestArgs),
+    /// Send the requests in a .http file
+    Run(FileArgs),
+    /// Send the requests in a .http file and fail on error statuses
+    Test(FileArgs),
+    /// Convert a curl command into a .http request
+    Import {
+        /// File with the curl command, stdin if missing or `-`
+        file: Option<PathBuf>,
+        /// Name for the request, written after `###`
+        #[arg(long, default_value = "")]
+        name: String,
+    },
+    /// Print the requests in a .http file as curl commands
+    Export {
+        file: PathBuf,
+        /// Only export the requests with this name
+        #[arg(short, long = "name", value_name = "NAME")]
+        names: Vec<String>,
+    },
+    /// Print the completion script for a shell
+    Completions { shell: Shell },
+}
+
+#[derive(Args, Debug)]
+struct RequestArgs {
+    /// The optional method and the URL, then `Header: value` headers and
+    /// `key=value` JSON body fields. `:8080/path` is localhost
+    #[arg(required = true, value_name = "[METHOD] URL [ITEM]")]
+    request: Vec<String>,
+    /// Scheme for URLs without one, http unless called as `https`
+    #[arg(long, value_name = "SCHEME", value_parser = ["http", "https"])]
+    default_scheme: Option<String>,
+    #[command(flatten)]
+    options: Options,
+}
+
+#[derive(Args, Debug)]
+struct FileArgs {
+    file: PathBuf,
+    /// Only send the requests with this name
+    #[arg(short, long = "name", value_name = "NAME")]
+    names: Vec<String>,
+    #[command(flatten)]
+    options: Options,
+}
+
+/// Settings shared by the commands that send requests.
+#[derive(Args, Debug)]
+struct Options {
+    /// Save the response body to FILE
+    #[arg(short = 'O', value_name = "FILE")]
+    output: Option<String>,
+    /// Don't ask for a compressed body, and keep the body as sent
+    #[arg(long)]
+    raw: bool,
+    /// Ask for a compressed body, even with --raw
+    #[arg(long)]
+    compressed: bool,
+    /// Show the connection: address, ALPN and server certificate
+    #[arg(long)]
+    tls_info: bool,
+    /// Keep headers and cookies in a named session
+    #[arg(long, value_name = "NAME")]
+    session: Option<String>,
+    /// Load and save cookies in FILE (Netscape format, or JSON for .json)
+    #[arg(long, value_name = "FILE")]
+    cookie_jar: Option<String>,
+
+    /// Credentials, asks for the password if it's missing
+    #[arg(short = 'a', long, value_name = "USER[:PASS]", help_heading = "Auth")]
+    auth: Option<String>,
+    /// How the credentials are sent, basic by default
+    #[arg(long, value_name = "TYPE", help_heading = "Auth",
+          value_parser = ["basic", "digest", "bearer", "oauth2"])]
+    auth_type: Option<String>,
+    /// OAuth2 token endpoint
+    #[arg(long, value_name = "URL", help_heading = "Auth")]
+    token_url: Option<String>,
+    /// OAuth2 client id
+    #[arg(long, value_name = "ID", help_heading = "Auth")]
+    client_id: Option<String>,
+    /// OAuth2 client secret
+    #[arg(long, value_name = "SECRET", help_heading = "Auth")]
+    client_secret: Option<String>,
+    /// Use the refresh_token grant with TOKEN
+    #[arg(long, value_name = "TOKEN", help_heading = "Auth")]
+    refresh_token: Option<String>,
+    /// OAuth2 scopes, space separated
+    #[arg(long, help_heading = "Auth")]
+    scope: Option<String>,
+    /// Sign with AWS SigV4, keys from --aws-key or AWS_* variables
+    #[arg(long, value_name = "REGION:SERVICE", help_heading = "Auth")]
+    aws_sigv4: Option<String>,
+    /// AWS access and secret keys
+    #[arg(long, value_name = "ACCESS:SECRET", help_heading = "Auth")]
+    aws_key: Option<String>,
+    /// Sign with HMAC-SHA256 in this header
+    #[arg(long, value_name = "NAME", help_heading = "Auth")]
+    hmac_header: Option<String>,
+    /// Key for --hmac-header
+    #[arg(long, value_name = "SECRET", help_heading = "Auth")]
+    hmac_secret: Option<String>,
+    /// Text before the signature, like `sha256=`
+    #[arg(long, value_name = "PREFIX", help_heading = "Auth")]
+    hmac_prefix: Option<String>,
+    /// How the signature is written, hex by default
+    #[arg(long, value_name = "ENCODING", help_heading = "Auth",
+          value_parser = ["hex", "base64"])]
+    hmac_encoding: Option<String>,
+
+    /// Trust the CA certificates in FILE
+    #[arg(long, value_name = "FILE", help_heading = "TLS")]
+    cacert: Option<String>,
+    /// Client certificate, PEM or .p12
+    #[arg(long, value_name = "FILE", help_heading = "TLS")]
+    cert: Option<String>,
+    /// Private key for a PEM --cert
+    #[arg(long, value_name = "FILE", help_heading = "TLS")]
+    key: Option<String>,
+    /// Password for a .p12 --cert
+    #[arg(long, help_heading = "TLS")]
+    pass: Option<String>,
+    /// Skip certificate verification
+    #[arg(short = 'k', long, help_heading = "TLS")]
+    insecure: bool,
+    /// Lowest TLS version: 1.0, 1.1, 1.2 or 1.3
+    #[arg(long, value_name = "VERSION", help_heading = "TLS", value_parser = TlsVersion::from_str)]
+    tls_min: Option<TlsVersion>,
+    /// Highest TLS version
+    #[arg(long, value_name = "VERSION", help_heading = "TLS", value_parser = TlsVersion::from_str)]
+    tls_max: Option<TlsVersion>,
+    /// Server name to send instead of the URL host
+    #[arg(long, value_name = "NAME", help_heading = "TLS")]
+    sni: Option<String>,
+
+    /// http://, https://, socks5:// or socks5h:// proxy
+    #[arg(long, value_name = "URL", help_heading = "Connection")]
+    proxy: Option<String>,
+    /// Hosts reached without the proxy, replaces NO_PROXY
+    #[arg(long, value_name = "HOSTS", help_heading = "Connection")]
+    noproxy: Option<String>,
+    /// Connect to a Unix socket instead of the host
+    #[arg(long, value_name = "PATH", help_heading = "Connection")]
+    unix_socket: Option<PathBuf>,
+    /// Only use IPv4 addresses
+    #[arg(short = '4', help_heading = "Connection", conflicts_with = "ipv6")]
+    ipv4: bool,
+    /// Only use IPv6 addresses
+    #[arg(short = '6', help_heading = "Connection")]
+    ipv6: bool,
+    /// Connect to ADDR for HOST:PORT
+    #[arg(long, value_name = "HOST:PORT:ADDR", help_heading = "Connection")]
+    resolve: Option<String>,
+    /// Connect to HOST2:PORT2 for HOST1:PORT1
+    #[arg(
+        long,
+        value_name = "HOST1:PORT1:HOST2:PORT2",
+        help_heading = "Connection"
+    )]
+    connect_to: Option<String>,
+    /// Extra hosts file
+    #[arg(long, value_name = "PATH", help_heading = "Connection")]
+    hosts_file: Option<String>,
+
+    /// Retry transient failures up to N times
+    #[arg(long, value_name = "N", help_heading = "Retry")]
+    retry: Option<u32>,
+    /// First wait between retries, 1 by default
+    #[arg(long, value_name = "SECS", help_heading = "Retry", requires = "retry",
+          value_parser = parse_seconds)]
+    retry_delay: Option<Duration>,
+    /// Give up retrying after this long
+    #[arg(long, value_name = "SECS", help_heading = "Retry", requires = "retry",
+          value_parser = parse_seconds)]
+    retry_max_time: Option<Duration>,
+    /// Also retry methods that aren't idempotent
+    #[arg(long, help_heading = "Retry", requires = "retry")]
+    retry_all_methods: bool,
+}
+
+/// Inserts the `request` command when the arguments start with anything
+/// else, so `Sip GET url` keeps working.
+fn with_default_command(mut args: Vec<String>) -> Vec<String> {
+    let command = Cli::command();
+    let explicit = args.get(1).is_none_or(|first| {
+        matches!(
+            first.as_str(),
+            "help" | "-h" | "--help" | "-V" | "--version"
+        ) || command.find_subcommand(first).is_some()
+    });
+    if !explicit {
+        args.insert(1, "request".to_string());
+    }
+    args
+}
+
+/// Builds the request text and the JSON body from the positional arguments:
+/// the method and URL, then `Header: value` headers and `key=value` body
+/// fields.
+///
+/// Without a method it's GET, or POST when there are body fields. A body
+/// comes with JSON `Content-Type` and `Accept` headers, unless given.
+fn request_from_items(
+    items: &[String],
+    default_scheme: &str,
+) -> Result<(String, String), &'static str> {
+    let (method, items) = match items.split_first() {
+        Some((first, rest)) if is_method(first) => (Some(first.as_str()), rest),
+        _ => (None, items),
+    };
+    let (url, items) = items.split_first().ok_or("Missing URL")?;
+    let mut headers = String::new();
+    let mut fields = Vec::new();
+    for item in items {
+        match (item.find('='), item.find(':')) {
+            (Some(eq), colon) if colon.is_none_or(|colon| eq < colon) => {
+                let (k, v) = item.split_at(eq);
+                let (k, v) = (Value::from(k), Value::from(&v[1..]));
+                fields.push(format!("{}:{}", k, v));
             }
-            let request = HttpRequest::parse(raw_request.clone());
-            if let Ok(request) = request {
-                result.push(("".to_string(), request));
+            _ => {
+                headers.push_str(item);
+                headers.push('\n');
             }
-            raw_request = String::new();
-            continue;
-        }
-        if line.starts_with('#') {
-            continue;
-        }
-        if line.starts_with("@") {
-            let line = line.strip_prefix('@').unwrap();
-            let (k, v) = line.split_once("=").unwrap();
-            let k = k.trim().to_string();
-            let v = v.trim().to_string();
-            vars.insert(k, v);
-            continue;
-        }
-        raw_request.push_str(line);
-        raw_request.push('\n');
-    }
-    result
-}
-
-fn args_parser() -> (String, String, HashMap<String, String>) {
-    let mut input = String::new();
-    let mut body = String::new();
-    let mut args: HashMap<String, String> = HashMap::new();
-    let mut skip = 1;
-    let mut k_arg = String::new();
-    for arg in env::args().skip(1) {
-        if arg.starts_with("-") {
-            k_arg = arg.strip_prefix("-").unwrap().to_string();
-            k_arg = k_arg.strip_prefix("-").unwrap_or(&k_arg).to_string();
-            skip += 1;
-        } else if !k_arg.is_empty() {
-            args.insert(k_arg.clone(), arg.clone());
-            k_arg = String::new();
-            skip += 1;
-        } else {
-            break;
         }
     }
-    // let mut body = String::new();
-    for arg in env::args().skip(skip) {
-        let is_empty = input.is_empty();
+    if !fields.is_empty() {
+        // Like HTTPie, unless the headers say otherwise
+        let has = |name: &str| {
+            headers.lines().any(|line| {
+                line.split_once(':')
+                    .is_some_and(|(k, _)| k.trim().eq_ignore_ascii_case(name))
+            })
+        };
+        let (content_type, accept) = (has("Content-Type"), has("Accept"));
+        if !content_type {
+            headers.push_str("Content-Type: application/json\n");
+        }
+        if !accept {
+            headers.push_str("Accept: application/json, */*;q=0.5\n");
+        }
+    }
+    let method = method.unwrap_or(if fields.is_empty() { "GET" } else { "POST" });
+    let input = format!(
+        "{} {}\n{}",
+        method,
+        expand_url(url, default_scheme),
+        headers
+    );
+    let body = match fields.is_empty() {
+        true => String::new(),
+        false => format!("{{{}}}", fields.join(",")),
+    };
+    Ok((input, body))
+}
+
+/// Returns true for a method: a standard one in any case, or a custom one in
+/// capitals like `PURGE`.
+fn is_method(item: &str) -> bool {
+    item.bytes().all(|b| b.is_ascii_alphabetic())
+        && (item.bytes().all(|b| b.is_ascii_uppercase())
+            || !matches!(HttpMethod::from_str(item), HttpMethod::Other(_)))
+}
+
+/// Expands `:8080/path` to `localhost:8080/path` and adds `default_scheme`
+/// to URLs without a scheme.
+fn expand_url(url: &str, default_scheme: &str) -> String {
+    let url = match url.strip_prefix(':') {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => format!("localhost{}", url),
+        Some(rest) => format!("localhost{}", rest),
+        None => url.to_string(),
+    };
+    let scheme = url.split_once("://").filter(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    });
+    match scheme {
+        // The request parser only knows lowercase schemes
+        Some((scheme, rest)) => format!("{}://{}", scheme.to_ascii_lowercase(), rest),
+        None => format!("{}://{}", default_scheme, url),
+    }
+}
+
+/// Returns true when the binary was called `https`, which makes it the
+/// default scheme like HTTPie's `https` command.
+fn invoked_as_https() -> bool {
+    env::args()
+        .next()
+        .and_then(|arg0| Path::new(&arg0).file_stem().map(|stem| stem == "https"))
+        .unwrap_or(false)
+}
+
+/// Builds the authentication from `-a user:pass` and `--auth-type`.
+///
+/// Asks for the password when only the user is given.
+fn auth_from_args(options: &Options) -> Result<Option<Box<dyn Auth>>, &'static str> {
+    #[cfg(feature = "oauth2")]
+    if options.auth_type.as_deref() == Some("oauth2") {
+        return oauth_from_args(options).map(|auth| Some(Box::new(auth) as Box<dyn Auth>));
+    }
+    let Some(credentials) = &options.auth else {
+        return Ok(None);
+    };
+    let auth_type = options.auth_type.as_deref().unwrap_or("basic");
+    if auth_type == "bearer" {
+        return Ok(Some(Box::new(BearerAuth::new(credentials))));
+    }
+    let (user, password) = match credentials.split_once(':') {
+        Some((user, password)) => (user.to_string(), password.to_string()),
+        None => {
+            let password = rpassword::prompt_password(format!("Password for {}: ", credentials))
+                .map_err(|_| "Unable to read password")?;
+            (credentials.clone(), password)
+        }
+    };
+    match auth_type {
+        "basic" => Ok(Some(Box::new(BasicAuth::new(&user, &password)))),
+        "digest" => Ok(Some(Box::new(DigestAuth::new(&user, &password)))),
+        _ => Err("Unknown auth type"),
+    }
+}
+
+/// Builds the OAuth2 provider from `--token-url`, `--client-id`,
+/// `--client-secret`, `--scope` and either `-a user:pass` (password grant)
+/// or `--refresh-token`. Without them the client_credentials grant is used.
+/// The token request uses the same TLS and proxy settings as the request.
+#[cfg(feature = "oauth2")]
+fn oauth_from_args(options: &Options) -> Result<sip::http::OAuth2, &'static str> {
+    use sip::http::{Grant, OAuth2};
+    let token_url = options.token_url.as_ref().ok_or("Missing --token-url")?;
+    let client_id = options.client_id.as_ref().ok_or("Missing --client-id")?;
+    let grant = if let Some(credentials) = &options.auth {
+        let (user, password) = credentials
+            .split_once(':')
+            .ok_or("Expected -a user:password")?;
+        Grant::Password {
+            user: user.to_string(),
+            password: password.to_string(),
+        }
+    } else if let Some(refresh_token) = &options.refresh_token {
+        Grant::RefreshToken(refresh_token.clone())
+    } else {
+        Grant::ClientCredentials
+    };
+    let mut oauth = OAuth2::new(
+        token_url,
+        client_id,
+        options.client_secret.as_deref(),
+        grant,
+    );
+    if let Some(scope) = &options.scope {
+        oauth = oauth.scope(scope);
+    }
+    oauth = oauth.tls(tls_from_args(options)?);
+    let token_request = HttpRequest::parse(format!("POST {}", token_url))?;
+    if let Some(proxy) = proxy_from_args(options, &token_request)? {
+        oauth = oauth.proxy(proxy);
+    }
+    match oauth.default_cache_file() {
+        Some(path) => Ok(oauth.cache_file(path)),
+        None => Ok(oauth),
+    }
+}
 
-        if !is_empty && arg.contains('=') {
-            if body.is_empty() {
-                body.push('{');
+/// Builds the request signer from `--aws-sigv4 region:service` or
+/// `--hmac-header NAME --hmac-secret SECRET [--hmac-prefix PREFIX]
+/// [--hmac-encoding hex|base64]`.
+///
+/// AWS keys come from `--aws-key ACCESS:SECRET` or the usual `AWS_*`
+/// environment variables.
+fn signer_from_args(options: &Options) -> Result<Option<Arc<dyn Signer>>, &'static str> {
+    if let Some(scope) = &options.aws_sigv4 {
+        let (region, service) = scope
+            .split_once(':')
+            .ok_or("Expected --aws-sigv4 region:service")?;
+        let (access_key, secret_key) = match &options.aws_key {
+            Some(keys) => {
+                let (access_key, secret_key) = keys
+                    .split_once(':')
+                    .ok_or("Expected --aws-key access:secret")?;
+                (access_key.to_string(), secret_key.to_string())
             }
-            let (k, v) = arg.split_once('=').unwrap();
-            body.push_str(&format!("\"{}\":\"{}\"", k, v));
-            body.push(',');
-            continue;
-        }
-        input.push_str(&arg);
-        if !is_empty {
-            input.push('\n');
+            None => (
+                env::var("AWS_ACCESS_KEY_ID").map_err(|_| "Missing AWS_ACCESS_KEY_ID")?,
+                env::var("AWS_SECRET_ACCESS_KEY").map_err(|_| "Missing AWS_SECRET_ACCESS_KEY")?,
+            ),
+        };
+        let mut signer = AwsSigV4::new(&access_key, &secret_key, region, service);
+        if let Ok(token) = env::var("AWS_SESSION_TOKEN") {
+            signer = signer.session_token(&token);
+        }
+        return Ok(Some(Arc::new(signer)));
+    }
+    if let Some(header) = &options.hmac_header {
+        let secret = options
+            .hmac_secret
+            .as_ref()
+            .ok_or("Missing --hmac-secret")?;
+        let encoding = match options.hmac_encoding.as_deref() {
+            None | Some("hex") => SignatureEncoding::Hex,
+            Some("base64") => SignatureEncoding::Base64,
+            Some(_) => return Err("Expected --hmac-encoding hex or base64"),
+        };
+        let prefix = options.hmac_prefix.as_deref();
+        let signer = HmacSigner::new(header, secret)
+            .prefix(prefix.unwrap_or_default())
+            .encoding(encoding);
+        return Ok(Some(Arc::new(signer)));
+    }
+    Ok(None)
+}
+
+/// Builds the TLS settings from `--cacert`, `--cert`/`--key` (or a `.p12`
+/// `--cert` with `--pass`), `-k/--insecure`, `--tls-min`, `--tls-max` and
+/// `--sni`.
+fn tls_from_args(options: &Options) -> Result<TlsConfig, &'static str> {
+    let mut tls = TlsConfig::new();
+    if let Some(ca) = &options.cacert {
+        tls.ca_files.push(ca.into());
+    }
+    if let Some(cert) = &options.cert {
+        let lower = cert.to_lowercase();
+        tls.client_cert = if lower.ends_with(".p12") || lower.ends_with(".pfx") {
+            Some(ClientCert::Pkcs12 {
+                file: cert.into(),
+                password: options.pass.clone().unwrap_or_default(),
+            })
         } else {
-            input.push(' ');
+            let key = options.key.as_ref().ok_or("Missing --key for --cert")?;
+            Some(ClientCert::Pem {
+                cert: cert.into(),
+                key: key.into(),
+            })
+        };
+    }
+    tls.insecure = options.insecure;
+    tls.min_version = options.tls_min;
+    tls.max_version = options.tls_max;
+    tls.sni = options.sni.clone();
+    Ok(tls)
+}
+
+/// Returns the proxy from `--proxy URL`, or from the environment.
+///
+/// `--noproxy a,b` replaces the `NO_PROXY` list. A proxy in the environment
+/// that can't be used is skipped with a warning, only `--proxy` must parse.
+fn proxy_from_args(
+    options: &Options,
+    request: &HttpRequest,
+) -> Result<Option<Proxy>, &'static str> {
+    let no_proxy = match &options.noproxy {
+        Some(no_proxy) => parse_no_proxy(no_proxy),
+        None => env_no_proxy(),
+    };
+    let proxy = match &options.proxy {
+        Some(url) => Proxy::parse(url)?,
+        None => {
+            let (ssl, host, port) = request.endpoint()?;
+            if no_proxy_matches(&no_proxy, &host, port) {
+                return Ok(None);
+            }
+            match Proxy::from_env(ssl) {
+                Ok(Some(proxy)) => proxy,
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    eprintln!("Warning: ignoring the proxy in the environment: {}", e);
+                    return Ok(None);
+                }
+            }
         }
+    };
+    Ok(Some(Proxy { no_proxy, ..proxy }))
+}
+
+/// Builds the resolver from `--hosts-file PATH`, `--resolve host:port:addr`
+/// and `--connect-to host1:port1:host2:port2`, on top of the system one.
+fn resolver_from_args(options: &Options) -> Result<Option<Arc<dyn Resolver>>, &'static str> {
+    let mut resolver: Arc<dyn Resolver> = Arc::new(SystemResolver);
+    let mut custom = false;
+    if let Some(path) = &options.hosts_file {
+        resolver = Arc::new(HostsResolver::load(path.as_ref(), resolver)?);
+        custom = true;
     }
-    if body.starts_with('{') && !body.ends_with('}') {
-        body.pop();
-        body.push('}');
+    if options.resolve.is_some() || options.connect_to.is_some() {
+        let mut overrides = StaticResolver::new(resolver);
+        if let Some(spec) = &options.resolve {
+            let (host, port, addrs) = parse_resolve(spec)?;
+            overrides.insert(&host, port, addrs);
+        }
+        if let Some(spec) = &options.connect_to {
+            let ((from_host, from_port), (to_host, to_port)) = parse_connect_to(spec)?;
+            overrides.connect_to((&from_host, from_port), (&to_host, to_port));
+        }
+        resolver = Arc::new(overrides);
+        custom = true;
     }
+    Ok(custom.then_some(resolver))
+}
 
-    (input, body, args)
+/// Builds the retry policy from `--retry N`, `--retry-delay SECS`,
+/// `--retry-max-time SECS` and `--retry-all-methods`.
+fn retry_from_args(options: &Options) -> Option<RetryPolicy> {
+    let mut policy = RetryPolicy::new(options.retry?);
+    if let Some(delay) = options.retry_delay {
+        policy.delay = delay;
+    }
+    policy.max_time = options.retry_max_time;
+    policy.all_methods = options.retry_all_methods;
+    Some(policy)
 }
 
-fn main() {
-    let (input, body, args) = args_parser();
-    let request = HttpRequest::parse(input);
-    if request.is_err() {
-        println!("Error: {}", request.err().unwrap());
-        return;
+/// Parses a number of seconds like `1.5`, for `--retry-delay` and
+/// `--retry-max-time`.
+fn parse_seconds(value: &str) -> Result<Duration, &'static str> {
+    value
+        .parse::<f64>()
+        .ok()
+        .and_then(|s| Duration::try_from_secs_f64(s).ok())
+        .ok_or("Invalid number of seconds")
+}
+/// Sends `request` with the settings in `options`, keeping the session and
+/// the cookie jar up to date.
+fn send(
+    mut request: HttpRequest,
+    options: &Options,
+    auth: &mut Option<Box<dyn Auth>>,
+) -> Result<HttpResponse, &'static str> {
+    request.tls = tls_from_args(options)?;
+    request.proxy = proxy_from_args(options, &request)?;
+    if options.ipv4 {
+        request.ip_version = Some(IpVersion::V4);
+    } else if options.ipv6 {
+        request.ip_version = Some(IpVersion::V6);
     }
-    let mut request = request.unwrap();
+    if let Some(path) = &options.unix_socket {
+        request.unix_socket = Some(path.clone());
+    }
+    if let Some(resolver) = resolver_from_args(options)? {
+        request.resolver = resolver;
+    }
+    request.retry = retry_from_args(options);
+    request.signer = signer_from_args(options)?;
+
+    let mut session = None;
+    if let Some(name) = &options.session {
+        match Session::path_for(name, &request.host) {
+            Some(path) => match Session::load(&path) {
+                Ok(mut loaded) => {
+                    loaded.apply(&mut request);
+                    if let Some(auth) = auth.as_mut()
+                        && let Err(e) = auth.apply(&mut request)
+                    {
+                        eprintln!("Error: {}", e);
+                    }
+                    loaded.update_headers(&request.headers);
+                    session = Some((loaded, path));
+                }
+                Err(e) => eprintln!("Error: {}", e),
+            },
+            None => eprintln!("Error: no config directory for sessions"),
+        }
+    }
+
     request
         .headers
         .insert("User-Agent", &format!("Sip/{}", VERSION));
-    println!("{}", body);
-    request.body = body.as_bytes().to_vec();
-    if !body.is_empty() {
-        request
-            .headers
-            .insert("Content-Legth", body.len().to_string().as_str());
-    }
-
-    let response = request.brew();
-    if let Ok(response) = response {
-        print_response(&response);
-        if let Some(file) = args.get("O") {
-            save_file(file, response);
+    // --raw leaves Accept-Encoding out, unless --compressed asks for it
+    if let Some(encodings) = accept_encoding()
+        && (options.compressed || !options.raw)
+        && !request.headers.contains_key("accept-encoding")
+    {
+        request.headers.insert("Accept-Encoding", &encodings);
+    }
+    if !request.body.is_empty() {
+        let length = request.body.len().to_string();
+        request.headers.insert("Content-Length", &length);
+    }
+    if request.body.len() >= EXPECT_CONTINUE_SIZE && !request.headers.contains_key("expect") {
+        request.headers.insert("Expect", "100-continue");
+    }
+
+    let mut jar = None;
+    if let Some(path) = &options.cookie_jar {
+        match CookieJar::load(path) {
+            Ok(loaded) => {
+                loaded.apply(&mut request);
+                jar = Some(loaded);
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+
+    let mut response = match auth.as_mut() {
+        Some(auth) => request.brew_with_auth(auth.as_mut()),
+        None => request.brew(),
+    }?;
+    if let Some((session, path)) = session.as_mut() {
+        session.update(&request, &response);
+        if let Err(e) = session.save(path) {
+            eprintln!("Error: {}", e);
+        }
+    }
+    if let (Some(jar), Some(path)) = (jar.as_mut(), &options.cookie_jar) {
+        jar.store(&request, &response);
+        if let Err(e) = jar.save(path) {
+            eprintln!("Error: {}", e);
+        }
+    }
+    if !options.raw
+        && let Err(e) = response.decompress()
+    {
+        eprintln!("Error: {}", e);
+    }
+    Ok(response)
+}
+
+/// Prints the response and saves the body with `-O`.
+fn show(response: HttpResponse, options: &Options) {
+    if options.tls_info {
+        print_tls_info(&response);
+    }
+    print_response(&response);
+    if let Some(file) = &options.output {
+        save_file(file, response);
+    };
+}
+
+/// Reads the requests in a `.http` file, only those in `names` if any.
+fn load_requests(
+    file: &Path,
+    names: &[String],
+) -> Result<Vec<(String, HttpRequest)>, &'static str> {
+    let content = fs::read_to_string(file).map_err(|_| "Unable to read the .http file")?;
+    let requests: Vec<_> = parse_http_file(&content)
+        .into_iter()
+        .filter(|(name, _)| names.is_empty() || names.contains(name))
+        .collect();
+    if requests.is_empty() {
+        return Err("No matching requests in the .http file");
+    }
+    Ok(requests)
+}
+
+/// Names a request from a file in the output, `METHOD url` if it has no name.
+fn label(name: &str, request: &HttpRequest) -> String {
+    match name {
+        "" => format!("{} {}", request.method.to_str(), request.url()),
+        name => name.to_string(),
+    }
+}
+
+fn request_command(args: RequestArgs) -> Result<(), &'static str> {
+    let default_scheme = match &args.default_scheme {
+        Some(scheme) => scheme.as_str(),
+        None if invoked_as_https() => "https",
+        None => "http",
+    };
+    let (input, body) = request_from_items(&args.request, default_scheme)?;
+    let mut request = HttpRequest::parse(input)?;
+    request.body = body.into_bytes();
+    let mut auth = auth_from_args(&args.options)?;
+    let response = send(request, &args.options, &mut auth)?;
+    show(response, &args.options);
+    Ok(())
+}
+
+/// Sends the requests in order, stopping at the first that can't be sent.
+fn run_command(args: FileArgs) -> Result<(), &'static str> {
+    let requests = load_requests(&args.file, &args.names)?;
+    let mut auth = auth_from_args(&args.options)?;
+    for (name, request) in requests {
+        println!("### {}", label(&name, &request));
+        let response = send(request, &args.options, &mut auth)?;
+        show(response, &args.options);
+    }
+    Ok(())
+}
+
+/// Sends every request and reports which got a 4xx or 5xx, or no response.
+fn test_command(args: FileArgs) -> Result<(), &'static str> {
+    let requests = load_requests(&args.file, &args.names)?;
+    let mut auth = auth_from_args(&args.options)?;
+    let total = requests.len();
+    let mut failed = 0;
+    for (name, request) in requests {
+        let label = label(&name, &request);
+        match send(request, &args.options, &mut auth) {
+            Ok(response) if response.error_for_status_ref().is_ok() => {
+                println!("PASS {} {}", response.status.as_num(), label)
+            }
+            Ok(response) => {
+                failed += 1;
+                println!("FAIL {} {}", response.status.as_num(), label);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("FAIL {}: {}", label, e);
+            }
+        }
+    }
+    println!("{} passed, {} failed", total - failed, failed);
+    match failed {
+        0 => Ok(()),
+        _ => Err("Some requests failed"),
+    }
+}
+
+fn import_command(file: Option<PathBuf>, name: String) -> Result<(), &'static str> {
+    let command = match file {
+        Some(file) if file != Path::new("-") => fs::read_to_string(file).ok(),
+        _ => {
+            let mut command = String::new();
+            io::stdin().read_to_string(&mut command).ok();
+            Some(command)
+        }
+    }
+    .ok_or("Unable to read the curl command")?;
+    let request = parse_curl(&command)?;
+    print!("{}", to_http_file(&[(name, request)]));
+    Ok(())
+}
+
+fn export_command(file: &Path, names: &[String]) -> Result<(), &'static str> {
+    for (name, request) in load_requests(file, names)? {
+        if !name.is_empty() {
+            println!("# {}", name);
+        }
+        println!("{}", to_curl(&request));
+    }
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse_from(with_default_command(env::args().collect()));
+    let result = match cli.command {
+        Command::Request(args) => request_command(args),
+        Command::Run(args) => run_command(args),
+        Command::Test(args) => test_command(args),
+        Command::Import { file, name } => import_command(file, name),
+        Command::Export { file, names } => export_command(&file, &names),
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "Sip", &mut io::stdout());
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(String::from).collect()
+    }
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::parse_from(with_default_command(args("Sip GET host/a -k --retry 2")));
+        let Command::Request(request) = cli.command else {
+            panic!("expected the request command");
         };
-    } else {
-        println!("{:?}", response.err())
+        assert_eq!(request.request, ["GET", "host/a"]);
+        assert!(request.options.insecure);
+        assert_eq!(request.options.retry, Some(2));
+
+        let cli = Cli::parse_from(args("Sip request GET host --raw --compressed"));
+        let Command::Request(request) = cli.command else {
+            panic!("expected the request command");
+        };
+        assert!(request.options.raw && request.options.compressed);
+
+        let cli = Cli::parse_from(with_default_command(args("Sip test api.http -n login")));
+        assert!(matches!(cli.command, Command::Test(FileArgs { names, .. }) if names == ["login"]));
+
+        assert!(Cli::try_parse_from(args("Sip GET host --retry-delay 1")).is_err());
+        assert!(Cli::try_parse_from(args("Sip request GET host -4 -6")).is_err());
+        assert!(Cli::try_parse_from(args("Sip request GET host --tls-min 2")).is_err());
+    }
+
+    #[test]
+    fn test_request_from_items() {
+        let items = args("PUT host/a?x=1 Auth:b== name=tea n=1");
+        let (input, body) = request_from_items(&items, "http").unwrap();
+        assert_eq!(
+            input,
+            "PUT http://host/a?x=1\nAuth:b==\n\
+             Content-Type: application/json\nAccept: application/json, */*;q=0.5\n"
+        );
+        assert_eq!(body, r#"{"name":"tea","n":"1"}"#);
+
+        let items = vec![
+            "host".to_string(),
+            "accept:text/plain".to_string(),
+            r#"say="hi"\"#.to_string(),
+        ];
+        let (input, body) = request_from_items(&items, "http").unwrap();
+        assert_eq!(
+            input,
+            "POST http://host\naccept:text/plain\nContent-Type: application/json\n"
+        );
+        assert_eq!(body, r#"{"say":"\"hi\"\\"}"#);
+
+        let (input, body) = request_from_items(&args("get https://host"), "http").unwrap();
+        assert_eq!(input, "get https://host\n");
+        assert!(body.is_empty());
+
+        let (input, _) = request_from_items(&args("example.com"), "https").unwrap();
+        assert_eq!(input, "GET https://example.com\n");
+        let (input, _) = request_from_items(&args(":8080/users name=tea"), "http").unwrap();
+        assert!(input.starts_with("POST http://localhost:8080/users\n"));
+        assert!(request_from_items(&args("PURGE"), "http").is_err());
+    }
+
+    #[test]
+    fn test_expand_url() {
+        assert_eq!(expand_url(":", "http"), "http://localhost");
+        assert_eq!(expand_url(":/a", "http"), "http://localhost/a");
+        assert_eq!(expand_url(":3000", "https"), "https://localhost:3000");
+        assert_eq!(expand_url("HTTPS://host", "http"), "https://host");
+        assert_eq!(
+            expand_url("host/?next=http://other", "http"),
+            "http://host/?next=http://other"
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            use std::io::Write;
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = [0u8; 1024];
+            let n = stream.read(&mut head).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            String::from_utf8_lossy(&head[..n]).to_string()
+        });
+        let items = args(&format!("GET HTTP://127.0.0.1:{}/a", port));
+        let (input, _) = request_from_items(&items, "https").unwrap();
+        let response = HttpRequest::parse(input).unwrap().brew().unwrap();
+        assert_eq!(response.status.as_num(), 204);
+        assert!(server.join().unwrap().starts_with("GET /a HTTP/1.1\r\n"));
     }
 }
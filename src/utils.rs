@@ -1,6 +1,6 @@
 use std::{fs::File, io::Write, path::Path};
 
-use crate::http::HttpResponse;
+use sip::http::{ConnectionInfo, HttpResponse, MediaType, TlsInfo};
 
 pub fn save_file(file: &str, response: HttpResponse) {
     let path = Path::new(file);
@@ -17,80 +17,170 @@ pub fn save_file(file: &str, response: HttpResponse) {
 #[cfg(feature = "render_body")]
 fn render_body(response: &HttpResponse) {
     use html2text::config;
-    use serde_json::{Value, to_string_pretty};
-    if response.headers.contains_key("content-type") {
-        let content_type = response.headers.get("content-type").unwrap();
-        let content_type = content_type
-            .split_once(';')
-            .unwrap_or((content_type, content_type))
-            .0;
-        match content_type {
-            "application/json" => {
-                if let Ok(raw) = str::from_utf8(response.content.as_slice()) {
-                    if let Ok(value) = serde_json::from_str::<serde_json::Value>(raw) {
-                        let text = serde_json::to_string_pretty(&value).unwrap();
-                        println!("{}", text);
-                    } else {
-                        println!("{}", raw);
-                    }
-                } else {
-                    println!("Error priting body");
-                };
-            }
-            "text/html" => {
-                if let Ok(raw) = str::from_utf8(response.content.as_slice()) {
-                    if let Ok(value) = config::rich()
-                        .use_doc_css()
-                        .string_from_read(raw.as_bytes(), 80)
-                    {
-                        println!("{}", value);
-                    } else {
-                        println!("{}", raw);
-                    }
-                } else {
-                    println!("Error priting body");
-                };
-            }
-            _ => {
-                if response.content.len() > 1024 {
-                    println!("<Binary {}>", response.content.len())
-                } else {
-                    println!(
-                        "{}",
-                        str::from_utf8(response.content.as_slice()).unwrap_or("Error priting body")
-                    );
-                }
+    let content_type = response.content_type();
+    match (content_type, response.text()) {
+        (Some(media), Ok(text)) if media.is_json() => {
+            match serde_json::from_str::<serde_json::Value>(&text) {
+                Ok(value) => println!("{}", serde_json::to_string_pretty(&value).unwrap()),
+                Err(_) => println!("{}", text),
             }
         }
-    } else {
-        if response.content.len() > 1024 {
-            println!("<Binary {}>", response.content.len())
-        } else {
-            println!(
-                "{}",
-                str::from_utf8(response.content.as_slice()).unwrap_or("Error priting body")
-            );
+        (Some(media), Ok(text)) if media.essence() == "text/html" => {
+            match config::rich()
+                .use_doc_css()
+                .string_from_read(text.as_bytes(), 80)
+            {
+                Ok(value) => println!("{}", value),
+                Err(_) => println!("{}", text),
+            }
         }
+        _ => print_text(response, 1024),
     }
 }
 
 #[cfg(not(feature = "render_body"))]
 fn render_body(response: &HttpResponse) {
-    if response.content.len() > 1024 * 100 {
-        println!("<Binary {}>", response.content.len())
-    } else {
-        println!(
-            "{}",
-            str::from_utf8(response.content.as_slice()).unwrap_or("Error priting body")
-        );
+    print_text(response, 1024 * 100);
+}
+
+/// Prints the decoded body, or only its size if it's over `max_len` bytes
+/// or doesn't look like text.
+fn print_text(response: &HttpResponse, max_len: usize) {
+    println!("{}", body_text(response, max_len));
+}
+
+fn body_text(response: &HttpResponse, max_len: usize) -> String {
+    let size = response.content.len();
+    if size > max_len || !response.content_type().as_ref().is_none_or(is_textual) {
+        return format!("<binary {} bytes>", size);
     }
+    match response.text() {
+        // Replacement or control characters mean the body isn't text in its charset
+        Ok(text)
+            if text.chars().any(|c| {
+                c == char::REPLACEMENT_CHARACTER
+                    || (c.is_control() && !c.is_whitespace() && c != '\x1b')
+            }) =>
+        {
+            format!("<binary {} bytes>", size)
+        }
+        Ok(text) => text,
+        Err(_) => "Error priting body".to_string(),
+    }
+}
+
+/// Returns true for media types meant to be read as text.
+fn is_textual(media: &MediaType) -> bool {
+    media.kind == "text"
+        || media.is_json()
+        || media.subtype == "xml"
+        || media.subtype.ends_with("+xml")
+        || ["javascript", "ecmascript", "x-www-form-urlencoded"].contains(&media.subtype.as_str())
+}
+
+fn print_status(response: &HttpResponse) {
+    let reason = if response.reason.is_empty() {
+        response.status.as_str()
+    } else {
+        response.reason.as_str()
+    };
+    println!("{} {}", response.status.as_num(), reason);
 }
 
 pub fn print_response(response: &HttpResponse) {
-    println!("{} {}", response.status.as_num(), response.status.as_str());
+    for interim in response.informational.iter() {
+        print_status(interim);
+        for header in interim.headers.iter() {
+            println!("- {}: {}", header.0, header.1);
+        }
+    }
+    print_status(response);
     for header in response.headers.iter() {
         println!("- {}: {}", header.0, header.1);
     }
     println!("\n");
     render_body(response);
+    for trailer in response.trailers.iter() {
+        println!("- {}: {}", trailer.0, trailer.1);
+    }
+}
+
+/// Prints the handshake details and the server certificates.
+pub fn print_tls_info(response: &HttpResponse) {
+    let ConnectionInfo { remote_addr, tls } = &response.connection;
+    if let Some(addr) = remote_addr {
+        println!("* Connected to {}", addr);
+    }
+    let Some(tls) = tls else {
+        println!("* No TLS");
+        return;
+    };
+    let TlsInfo {
+        protocol,
+        cipher,
+        alpn,
+        certificates,
+        partial,
+    } = tls;
+    let missing = || match partial {
+        true => "not available with native-tls, build with rustls to see it".to_string(),
+        false => "unknown".to_string(),
+    };
+    println!("* Protocol: {}", protocol.clone().unwrap_or_else(missing));
+    println!("* Cipher: {}", cipher.clone().unwrap_or_else(missing));
+    println!(
+        "* ALPN: {}",
+        alpn.clone().unwrap_or_else(|| "none".to_string())
+    );
+    for (i, cert) in certificates.iter().enumerate() {
+        println!("* Certificate {}", i);
+        println!("*   Subject: {}", cert.subject);
+        println!("*   Issuer: {}", cert.issuer);
+        if !cert.sans.is_empty() {
+            println!("*   SANs: {}", cert.sans.join(", "));
+        }
+        println!("*   Not before: {}", cert.not_before);
+        println!("*   Not after: {}", cert.not_after);
+        println!("*   SHA-256: {}", cert.fingerprint);
+    }
+    if *partial {
+        println!("* Intermediate certificates: not available with native-tls");
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sip::http::HttpResponseBuilder;
+
+    fn response(content_type: Option<&str>, body: &[u8]) -> HttpResponse {
+        let mut head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n", body.len());
+        if let Some(value) = content_type {
+            head.push_str(&format!("Content-Type: {}\r\n", value));
+        }
+        head.push_str("\r\n");
+        let mut builder = HttpResponseBuilder::new();
+        builder.append(head.as_bytes()).unwrap();
+        builder.append(body).unwrap();
+        builder.get().unwrap()
+    }
+
+    #[test]
+    fn test_body_text() {
+        let text = response(Some("text/plain; charset=utf-8"), "café\n".as_bytes());
+        assert_eq!(body_text(&text, 1024), "café\n");
+        let json = response(Some("application/problem+json"), b"{}");
+        assert_eq!(body_text(&json, 1024), "{}");
+        let untyped = response(None, b"hello");
+        assert_eq!(body_text(&untyped, 1024), "hello");
+
+        let png = response(Some("image/png"), b"\x89PNG\r\n\x1a\n");
+        assert_eq!(body_text(&png, 1024), "<binary 8 bytes>");
+        let invalid = response(Some("text/plain"), b"caf\xE9");
+        assert_eq!(body_text(&invalid, 1024), "<binary 4 bytes>");
+        let latin1 = response(Some("text/plain; charset=latin1"), b"\0\x01\x02");
+        assert_eq!(body_text(&latin1, 1024), "<binary 3 bytes>");
+        assert_eq!(body_text(&text, 2), "<binary 6 bytes>");
+    }
 }